@@ -0,0 +1,128 @@
+use anyhow::Result;
+use derive_more::{Display, Error};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+/// The items a player owns, by name
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Bank {
+    items: HashSet<String>,
+}
+
+impl Bank {
+    pub fn from_names<S: Into<String>>(names: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            items: names.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Parses a bank export of one item per line, either a plain name or tab separated columns
+    /// of `id, name, quantity`, `id, name` or `name, quantity` as exported by the Bank Memory
+    /// plugin. A first line of several columns without any numbers is taken for the column titles,
+    /// and it and items with a quantity of zero are skipped.
+    ///
+    /// # Errors
+    /// Returns an error for the first line that does not fit any of the layouts, or whose layout
+    /// cannot be told apart, like two numbers
+    pub fn from_export(contents: &str) -> Result<Self, ExportError> {
+        let mut items = HashSet::new();
+        let lines = contents
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty());
+        for (index, (line, text)) in lines.enumerate() {
+            let columns: Vec<&str> = text.split('\t').map(str::trim).collect();
+            let is_number = |column: &str| column.parse::<u64>().is_ok();
+            if index == 0 && columns.len() > 1 && !columns.iter().any(|column| is_number(column)) {
+                continue;
+            }
+
+            let error = |reason| ExportError {
+                line,
+                text: text.to_owned(),
+                reason,
+            };
+            let (name, quantity) = match columns[..] {
+                [name] if !is_number(name) => (name, None),
+                [id, name] if is_number(id) && !is_number(name) => (name, None),
+                [name, quantity] if !is_number(name) && is_number(quantity) => {
+                    (name, Some(quantity))
+                }
+                [id, name, quantity] if is_number(id) && is_number(quantity) => {
+                    (name, Some(quantity))
+                }
+                [_] => return Err(error("an item id without a name")),
+                [_, _] => return Err(error("expected `id, name` or `name, quantity`")),
+                [_, _, _] => return Err(error("expected `id, name, quantity`")),
+                _ => return Err(error("too many columns")),
+            };
+            if name.is_empty() {
+                return Err(error("an empty item name"));
+            }
+            if quantity
+                .is_none_or(|quantity| quantity.parse::<u64>().is_ok_and(|quantity| quantity > 0))
+            {
+                items.insert(name.to_owned());
+            }
+        }
+
+        Ok(Self { items })
+    }
+
+    /// # Errors
+    /// Returns an error if the file cannot be read or is malformed
+    pub fn read_export(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::from_export(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.items.contains(name)
+    }
+
+    #[must_use]
+    pub fn with_item(mut self, name: &str) -> Self {
+        self.items.insert(name.to_owned());
+        self
+    }
+}
+
+/// A line of a bank export that cannot be read
+#[derive(Debug, Display, Error, Clone, PartialEq, Eq)]
+#[display(fmt = "line {line}: {reason}: `{text}`")]
+pub struct ExportError {
+    /// The line number, starting from 1
+    pub line: usize,
+    pub text: String,
+    pub reason: &'static str,
+}
+
+/// Item prices in GP, by name
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Prices {
+    by_name: HashMap<String, u64>,
+}
+
+impl Prices {
+    /// Parses a JSON object of item names to their price
+    ///
+    /// # Errors
+    /// Returns an error if the JSON is malformed
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        Ok(Self {
+            by_name: serde_json::from_str(json)?,
+        })
+    }
+
+    /// # Errors
+    /// Returns an error if the file cannot be read or is malformed
+    pub fn read(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::from_json(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn price(&self, name: &str) -> Option<u64> {
+        self.by_name.get(name).copied()
+    }
+}
@@ -0,0 +1,189 @@
+use crate::generics::{Fraction, Scalar};
+use serde::Deserialize;
+use std::cmp::{max, min};
+
+/// Chance for an attack roll to beat a defence roll
+pub fn hit_chance(accuracy_roll: Scalar, defence_roll: Scalar) -> f64 {
+    let accuracy_roll: f64 = (*accuracy_roll).into();
+    let defence_roll: f64 = (*defence_roll).into();
+
+    if defence_roll > accuracy_roll {
+        0.5 * accuracy_roll / (defence_roll + 1.0)
+    } else {
+        1f64 - (0.5 * (defence_roll + 2.0) / (accuracy_roll + 1.0))
+    }
+}
+
+/// Chance for either of two attack rolls to beat a defence roll, as used by Osmumten's fang
+pub fn double_roll_hit_chance(accuracy_roll: Scalar, defence_roll: Scalar) -> f64 {
+    let accuracy_roll: f64 = (*accuracy_roll).into();
+    let defence_roll: f64 = (*defence_roll).into();
+
+    if accuracy_roll > defence_roll {
+        1f64 - (defence_roll + 2.0) * (2.0 * defence_roll + 3.0)
+            / (6.0 * (accuracy_roll + 1.0) * (accuracy_roll + 1.0))
+    } else {
+        accuracy_roll * (4.0 * accuracy_roll + 5.0)
+            / (6.0 * (accuracy_roll + 1.0) * (defence_roll + 1.0))
+    }
+}
+
+/// The damage dealt by a successful hit, rolled uniformly between `min` and `max` inclusive
+/// unless it comes from a clamped roll
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HitRange {
+    pub min: Scalar,
+    pub max: Scalar,
+    #[serde(default)]
+    pub roll: Option<ClampedRoll>,
+}
+
+/// A roll from 0 to `max_roll` inclusive, multiplied by `damage` and then raised to the minimum
+/// or lowered to the cap of its hit range. Every roll below the minimum or above the cap lands
+/// on it, so the damage is not uniform between them.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClampedRoll {
+    pub max_roll: Scalar,
+    pub damage: Option<Fraction>,
+}
+
+impl HitRange {
+    pub fn new(min: Scalar, max: Scalar) -> Self {
+        Self {
+            min,
+            max,
+            roll: None,
+        }
+    }
+
+    pub fn up_to(max: Scalar) -> Self {
+        Self::new(0.into(), max)
+    }
+
+    /// The mean damage of a successful hit
+    pub fn average(&self) -> f64 {
+        match self.roll {
+            Some(roll) => {
+                let total: f64 = (0..=*roll.max_roll)
+                    .map(|rolled| {
+                        let rolled = Scalar::new(rolled);
+                        let damage = roll.damage.map_or(rolled, |damage| rolled * damage);
+                        f64::from(*damage.clamp(self.min, self.max))
+                    })
+                    .sum();
+                total / f64::from(*roll.max_roll + 1)
+            }
+            None => {
+                let min: f64 = (*self.min).into();
+                let max: f64 = (*self.max).into();
+                (min + max) / 2.0
+            }
+        }
+    }
+}
+
+/// The damage dealt by a hitsplat whose accuracy roll fails
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MissDamage {
+    #[default]
+    Zero,
+    /// The minimum of the hit range is dealt even on a miss
+    Minimum,
+}
+
+/// Adjustments to an attack's hit range declared in the data, such as the minimum damage and
+/// damage cap of the Dark bow's special attack
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+pub struct HitRangeModifier {
+    /// Multiplier applied to the max hit before the minimum and cap
+    #[serde(default)]
+    pub damage: Option<Fraction>,
+    #[serde(default)]
+    pub min_hit: Scalar,
+    #[serde(default)]
+    pub max_hit_cap: Option<Scalar>,
+    #[serde(default)]
+    pub on_miss: MissDamage,
+}
+
+impl HitRangeModifier {
+    /// The hit range of a roll up to `max_hit`, multiplied and then clamped to the minimum and cap
+    pub fn apply(&self, max_hit: Scalar) -> HitRange {
+        let mut capped = self.damage.map_or(max_hit, |damage| max_hit * damage);
+        if let Some(max_hit_cap) = self.max_hit_cap {
+            capped = min(capped, max_hit_cap);
+        }
+
+        HitRange {
+            min: self.min_hit,
+            max: max(capped, self.min_hit),
+            roll: Some(ClampedRoll {
+                max_roll: max_hit,
+                damage: self.damage,
+            }),
+        }
+    }
+}
+
+/// A single hitsplat of an attack, with its own accuracy roll and damage rolled from `range` on a
+/// successful hit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hitsplat {
+    pub accuracy: f64,
+    pub range: HitRange,
+    pub on_miss: MissDamage,
+}
+
+impl Hitsplat {
+    pub fn new(accuracy: f64, range: HitRange) -> Self {
+        Self {
+            accuracy,
+            range,
+            on_miss: MissDamage::Zero,
+        }
+    }
+
+    pub fn up_to(accuracy: f64, max_hit: Scalar) -> Self {
+        Self::new(accuracy, HitRange::up_to(max_hit))
+    }
+
+    pub fn expected_damage(&self) -> f64 {
+        let miss_damage: f64 = match self.on_miss {
+            MissDamage::Zero => 0.0,
+            MissDamage::Minimum => (*self.range.min).into(),
+        };
+        self.accuracy * self.range.average() + (1.0 - self.accuracy) * miss_damage
+    }
+}
+
+/// Hitsplats that replace an attack's regular hitsplats with probability `chance`, such as an
+/// enchanted bolt effect
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Proc {
+    pub chance: f64,
+    pub hitsplats: Vec<Hitsplat>,
+}
+
+/// The independent hitsplats dealt by a single attack, and the effect that may replace them
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AttackResult {
+    pub hitsplats: Vec<Hitsplat>,
+    pub proc_effect: Option<Proc>,
+}
+
+impl AttackResult {
+    pub fn expected_damage(&self) -> f64 {
+        let regular_damage: f64 = self.hitsplats.iter().map(Hitsplat::expected_damage).sum();
+
+        if let Some(proc_effect) = &self.proc_effect {
+            let proc_damage: f64 = proc_effect
+                .hitsplats
+                .iter()
+                .map(Hitsplat::expected_damage)
+                .sum();
+            (1.0 - proc_effect.chance) * regular_damage + proc_effect.chance * proc_damage
+        } else {
+            regular_damage
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use super::weapon_callbacks::Attribute;
+use crate::{
+    damage::{HitRange, Hitsplat},
+    generics::{Fraction, Scalar},
+    unit::{Enemy, EnemyAttribute, Player},
+};
+use serde::Deserialize;
+use std::cmp::min;
+
+/// An enchanted bolt's special effect and its base chance to activate on each attack
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct BoltProc {
+    pub chance: Fraction,
+    pub effect: BoltEffect,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoltEffect {
+    /// Lucky Lightning, extra damage based on the ranged level
+    Opal,
+    /// Sea Curse, extra damage based on the ranged level, more against fiery enemies
+    Pearl,
+    /// Blood Forfeit, damage based on the target's hitpoints
+    Ruby,
+    /// Armour Piercing, ignores defence and increases the max hit
+    Diamond,
+    /// Dragon's Breath, extra damage based on the ranged level unless the target is immune to fire
+    Dragonstone,
+    /// Life Leech, increases the max hit of accurate attacks against non-undead enemies
+    Onyx,
+}
+
+impl BoltProc {
+    /// Chance of the effect activating, increased by 10% with the Kandarin hard diary
+    pub fn chance(&self, player: &Player) -> f64 {
+        let dividend: f64 = self.chance.dividend.into();
+        let divisor: f64 = self.chance.divisor.into();
+        let chance = dividend / divisor;
+
+        if player.extra.kandarin_hard_diary {
+            chance * 1.1
+        } else {
+            chance
+        }
+    }
+
+    /// The hitsplat dealt instead of a regular attack when the effect activates, or `None` if the
+    /// effect cannot activate against the enemy. The Zaryte crossbow strengthens every effect.
+    pub fn hitsplat(
+        &self,
+        player: &Player,
+        enemy: &Enemy,
+        accuracy: f64,
+        max_hit: Scalar,
+    ) -> Option<Hitsplat> {
+        let zaryte = player
+            .equipped()
+            .wielded
+            .weapon_has_attribute(&Attribute::ZaryteCrossbow);
+        let boosted = |normal: (i32, i32), zaryte_crossbow: (i32, i32)| {
+            let (dividend, divisor) = if zaryte { zaryte_crossbow } else { normal };
+            Fraction::new(dividend, divisor)
+        };
+        let bonus_damage =
+            |bonus: Scalar| Hitsplat::new(1.0, HitRange::new(bonus, max_hit + bonus));
+
+        match self.effect {
+            BoltEffect::Opal => Some(bonus_damage(
+                player.levels.ranged * boosted((1, 10), (11, 100)),
+            )),
+            BoltEffect::Pearl => {
+                let fraction = if enemy.has_attribute(&EnemyAttribute::Fiery) {
+                    boosted((1, 15), (11, 150))
+                } else {
+                    boosted((1, 20), (11, 200))
+                };
+                Some(bonus_damage(player.levels.ranged * fraction))
+            }
+            BoltEffect::Ruby => {
+                let damage = min(
+                    enemy.levels.hitpoints * boosted((1, 5), (11, 50)),
+                    if zaryte { 110 } else { 100 }.into(),
+                );
+                Some(Hitsplat::new(1.0, HitRange::new(damage, damage)))
+            }
+            BoltEffect::Diamond => Some(Hitsplat::up_to(
+                1.0,
+                max_hit * boosted((115, 100), (126, 100)),
+            )),
+            BoltEffect::Dragonstone => (!enemy.has_attribute(&EnemyAttribute::Dragon)
+                && !enemy.has_attribute(&EnemyAttribute::Fiery))
+            .then(|| bonus_damage(player.levels.ranged * boosted((1, 5), (11, 50)))),
+            BoltEffect::Onyx => (!enemy.has_attribute(&EnemyAttribute::Undead))
+                .then(|| Hitsplat::up_to(accuracy, max_hit * boosted((120, 100), (132, 100)))),
+        }
+    }
+}
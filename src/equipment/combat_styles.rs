@@ -1,8 +1,10 @@
-use crate::generics::{Scalar, Ticks, Tiles};
-use anyhow::{anyhow, Result};
+use crate::{
+    error::{Error, Result},
+    generics::{Scalar, Ticks, Tiles},
+};
 use serde::Deserialize;
 
-#[derive(Debug, Clone, Default, Copy)]
+#[derive(Debug, Clone, Default, Copy, PartialEq, Eq)]
 pub enum StyleType {
     Slash,
     #[default]
@@ -25,7 +27,7 @@ impl StyleType {
     }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Copy, PartialEq, Eq)]
 pub enum WeaponStyle {
     #[default]
     Accurate,
@@ -79,6 +81,30 @@ impl CombatOption {
         }
     }
 
+    /// How closely the option matches another, for keeping an equivalent style when switching
+    /// weapons. The same style type and weapon style match best, followed by the same weapon style
+    /// with a style type of the same class, then the same weapon style alone.
+    pub fn equivalence(&self, other: &Self) -> u8 {
+        let same_class =
+            |lhs: StyleType, rhs: StyleType| (lhs.is_melee() && rhs.is_melee()) || lhs == rhs;
+        if self.weapon_style != other.weapon_style {
+            0
+        } else if self.style_type == other.style_type {
+            3
+        } else if same_class(self.style_type, other.style_type) {
+            2
+        } else {
+            1
+        }
+    }
+
+    pub fn is_autocast(&self) -> bool {
+        matches!(
+            self.weapon_style,
+            WeaponStyle::Autocast | WeaponStyle::DefensiveAutocast
+        )
+    }
+
     /// # Errors
     /// Returns an error if `style_type` and `weapon_style` are incompatible, e.g. Slash, Rapid
     pub fn invisible_boost(&self) -> Result<CombatOptionModifier> {
@@ -115,7 +141,12 @@ impl CombatOption {
             }
             (StyleType::Magic, WeaponStyle::Autocast | WeaponStyle::DefensiveAutocast)
             | (StyleType::None, WeaponStyle::None) => (),
-            _ => return Err(anyhow!("Not interested")),
+            _ => {
+                return Err(Error::IncompatibleCombatOption {
+                    style_type: self.style_type,
+                    weapon_style: self.weapon_style,
+                })
+            }
         };
         Ok(boost)
     }
@@ -1,14 +1,23 @@
 #![allow(clippy::needless_update)]
+pub mod bolt_effects;
 pub mod combat_styles;
 mod default_items;
 pub(crate) mod weapon_callbacks;
 
+pub use self::weapon_callbacks::{Attribute, BonusGroup};
+
 use self::{
+    bolt_effects::BoltProc,
     combat_styles::{CombatOption, WeaponType},
-    weapon_callbacks::Attribute,
 };
-use crate::generics::{NamedData, Percentage, Scalar, Ticks, Tiles};
+use crate::{
+    damage::HitRangeModifier,
+    error,
+    generics::{NamedData, Percentage, Scalar, Ticks, Tiles},
+    spells::{Spell, Spellbook},
+};
 use serde::Deserialize;
+use std::collections::HashMap;
 
 #[allow(clippy::module_name_repetitions)]
 pub trait ContainsEquipment: for<'a> Deserialize<'a> {
@@ -65,7 +74,15 @@ macro_rules! weapon_struct {
                 #[serde(flatten)]
                 pub inner: Equipment,
                 pub weapon_stats: WeaponStats,
-                pub powered_staff_type: Option<PoweredStaff>
+                pub powered_staff_type: Option<PoweredStaff>,
+                #[serde(default)]
+                pub autocast: Vec<Spellbook>,
+                #[serde(default)]
+                pub ammunition: Option<AmmunitionRequirement>,
+                #[serde(default)]
+                pub hit_range: Option<HitRangeModifier>,
+                #[serde(default)]
+                pub special_attack: Option<SpecialAttack>,
             }
 
             impl ContainsEquipment for $struct_name {
@@ -90,6 +107,10 @@ macro_rules! weapon_struct {
                         },
                         weapon_stats: WeaponStats::default(),
                         powered_staff_type: None,
+                        autocast: Vec::default(),
+                        ammunition: None,
+                        hit_range: None,
+                        special_attack: None,
                     }
                 }
             }
@@ -97,9 +118,80 @@ macro_rules! weapon_struct {
     };
 }
 
-equipment_struct!(Head, Cape, Neck, Ammunition, Shield, Body, Legs, Hands, Feet, Ring);
+equipment_struct!(Head, Cape, Neck, Shield, Body, Legs, Hands, Feet, Ring);
 weapon_struct!(WeaponOneHanded, WeaponTwoHanded);
 
+#[derive(Deserialize, Debug, Clone)]
+pub struct Ammunition {
+    #[serde(flatten)]
+    pub inner: Equipment,
+    #[serde(default)]
+    pub projectile: Option<Projectile>,
+    #[serde(default)]
+    pub bolt_proc: Option<BoltProc>,
+}
+
+impl ContainsEquipment for Ammunition {
+    fn inner(&self) -> &Equipment {
+        &self.inner
+    }
+}
+
+impl Default for Ammunition {
+    fn default() -> Self {
+        Self {
+            inner: Equipment {
+                name: "Empty".to_owned(),
+                stats: Stats::default(),
+                attributes: Vec::default(),
+            },
+            projectile: None,
+            bolt_proc: None,
+        }
+    }
+}
+
+/// Hit range adjustments of a weapon's special attack
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct SpecialAttack {
+    pub hit_range: HitRangeModifier,
+    /// Replaces `hit_range` when firing the named ammunition
+    #[serde(default)]
+    pub ammunition: HashMap<String, HitRangeModifier>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum AmmunitionClass {
+    Arrow,
+    Bolt,
+    Dart,
+}
+
+/// The class of an ammunition item and its tier, the ranged level required to fire it
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub struct Projectile {
+    pub class: AmmunitionClass,
+    pub tier: Scalar,
+}
+
+/// The ammunition a weapon fires, up to and including `max_tier`. Embedded ammunition is loaded
+/// into the weapon itself, like darts in a blowpipe, instead of taken from the ammunition slot.
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub struct AmmunitionRequirement {
+    pub class: AmmunitionClass,
+    pub max_tier: Scalar,
+    #[serde(default)]
+    pub embedded: bool,
+}
+
+impl AmmunitionRequirement {
+    pub fn accepts(&self, ammunition: &Ammunition) -> bool {
+        ammunition.projectile.is_some_and(|projectile| {
+            projectile.class == self.class && projectile.tier <= self.max_tier
+        })
+    }
+}
+
 #[derive(Debug, Deserialize, Clone, Copy)]
 pub enum PoweredStaff {
     StarterStaff,
@@ -190,20 +282,61 @@ impl<'a> Wielded<'a> {
         }
     }
 
-    pub fn attack_speed(&self, combat_style: &CombatOption) -> Ticks {
-        let tick_offset = combat_style
-            .invisible_boost()
-            .expect("Valid combat style")
-            .attack_speed;
+    /// Attack speed with the given combat style, against another player if `pvp` is set
+    ///
+    /// # Errors
+    /// Returns an error if the combat style is invalid
+    pub fn attack_speed(&self, combat_style: &CombatOption, pvp: bool) -> error::Result<Ticks> {
+        let tick_offset = combat_style.invisible_boost()?.attack_speed;
+
+        let weapon_stats = self.weapon_stats();
+        let weapon_attack_speed = if pvp {
+            weapon_stats
+                .pvp_attack_speed
+                .unwrap_or(weapon_stats.attack_speed)
+        } else {
+            weapon_stats.attack_speed
+        };
+
+        Ok(weapon_attack_speed + tick_offset)
+    }
 
-        let weapon_attack_speed = match self {
+    /// Adjustments to the hit range of the wielded weapon's regular attacks
+    pub fn hit_range_modifier(&self) -> Option<HitRangeModifier> {
+        match self {
+            Self::OneHanded { weapon, shield: _ } => weapon.unwrap_or_default().hit_range,
+            Self::TwoHanded { weapon } => weapon.unwrap_or_default().hit_range,
+        }
+    }
+
+    pub fn special_attack(&self) -> Option<&SpecialAttack> {
+        match self {
             Self::OneHanded { weapon, shield: _ } => {
-                weapon.unwrap_or_default().weapon_stats.attack_speed
+                weapon.unwrap_or_default().special_attack.as_ref()
             }
-            Self::TwoHanded { weapon } => weapon.unwrap_or_default().weapon_stats.attack_speed,
+            Self::TwoHanded { weapon } => weapon.unwrap_or_default().special_attack.as_ref(),
+        }
+    }
+
+    /// The ammunition the wielded weapon fires, `None` for weapons that do not use the
+    /// ammunition slot
+    pub fn ammunition_requirement(&self) -> Option<AmmunitionRequirement> {
+        match self {
+            Self::OneHanded { weapon, shield: _ } => weapon.unwrap_or_default().ammunition,
+            Self::TwoHanded { weapon } => weapon.unwrap_or_default().ammunition,
+        }
+    }
+
+    /// Whether the wielded weapon has autocast styles and can autocast spells from the
+    /// spell's spellbook
+    pub fn can_autocast(&self, spell: &Spell) -> bool {
+        let autocast = match self {
+            Self::OneHanded { weapon, shield: _ } => &weapon.unwrap_or_default().autocast,
+            Self::TwoHanded { weapon } => &weapon.unwrap_or_default().autocast,
         };
 
-        weapon_attack_speed + tick_offset
+        autocast.contains(&spell.spellbook)
+            && self.combat_boost().iter().any(CombatOption::is_autocast)
     }
 
     pub fn attributes(&self) -> &Vec<Attribute> {
@@ -254,6 +387,51 @@ impl ContainsEquipment for Slots {
     }
 }
 
+impl Slots {
+    /// The slot the item is worn in, one and two handed weapons sharing the weapon slot
+    pub fn slot(&self) -> Slot {
+        match self {
+            Self::Head(_) => Slot::Head,
+            Self::Cape(_) => Slot::Cape,
+            Self::Neck(_) => Slot::Neck,
+            Self::Ammunition(_) => Slot::Ammunition,
+            Self::WeaponOneHanded(_) | Self::WeaponTwoHanded(_) => Slot::Weapon,
+            Self::Shield(_) => Slot::Shield,
+            Self::Body(_) => Slot::Body,
+            Self::Legs(_) => Slot::Legs,
+            Self::Hands(_) => Slot::Hands,
+            Self::Feet(_) => Slot::Feet,
+            Self::Ring(_) => Slot::Ring,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, derive_more::Display)]
+pub enum Slot {
+    #[display(fmt = "head")]
+    Head,
+    #[display(fmt = "cape")]
+    Cape,
+    #[display(fmt = "neck")]
+    Neck,
+    #[display(fmt = "ammunition")]
+    Ammunition,
+    #[display(fmt = "weapon")]
+    Weapon,
+    #[display(fmt = "shield")]
+    Shield,
+    #[display(fmt = "body")]
+    Body,
+    #[display(fmt = "legs")]
+    Legs,
+    #[display(fmt = "hands")]
+    Hands,
+    #[display(fmt = "feet")]
+    Feet,
+    #[display(fmt = "ring")]
+    Ring,
+}
+
 impl NamedData for Slots {
     fn get_name(&self) -> &str {
         &self.inner().name
@@ -303,6 +481,9 @@ pub struct WeaponStats {
     pub weapon_type: WeaponType,
     pub attack_speed: Ticks,
     pub range: Tiles,
+    /// Attack speed against other players, if it differs from the speed against NPCs
+    #[serde(default)]
+    pub pvp_attack_speed: Option<Ticks>,
 }
 
 impl Default for WeaponStats {
@@ -311,6 +492,7 @@ impl Default for WeaponStats {
             weapon_type: WeaponType::Unarmed,
             attack_speed: 4.into(),
             range: 1.into(),
+            pvp_attack_speed: None,
         }
     }
 }
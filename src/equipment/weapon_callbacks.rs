@@ -1,11 +1,14 @@
 use self::callbacks::{
-    arclight, black_mask, black_mask_imbued, blisterwood_accuracy, blisterwood_flail_max_hit,
-    blisterwood_sickle_max_hit, colossal_blade, dragon_hunter_crossbow_accuracy,
-    dragon_hunter_crossbow_max_hit, harmonised_nightmare_staff_attack_speed, identity,
-    salve_amulet, salve_amulet_enchanted, salve_amulet_enchanted_imbued, salve_amulet_imbued,
-    wilderness_weapon_magic, wilderness_weapon_melee, wilderness_weapon_ranged,
+    arclight, black_mask, black_mask_applies, black_mask_imbued, black_mask_imbued_applies,
+    blisterwood_accuracy, blisterwood_flail_max_hit, blisterwood_sickle_max_hit, colossal_blade,
+    dragon_hunter_crossbow_accuracy, dragon_hunter_crossbow_max_hit,
+    harmonised_nightmare_staff_attack_speed, identity, salve_amulet, salve_amulet_applies,
+    salve_amulet_enchanted, salve_amulet_enchanted_imbued, salve_amulet_imbued,
+    salve_amulet_imbued_applies, wilderness_weapon_magic, wilderness_weapon_melee,
+    wilderness_weapon_ranged,
 };
 use crate::{
+    explain::{StepKind, Trace},
     generics::{Scalar, Ticks},
     unit::{Enemy, Player},
 };
@@ -45,9 +48,48 @@ pub enum Attribute {
     WildernessWeaponMelee,
     WildernessWeaponRanged,
     WildernessWeaponMagic,
+    ScytheOfVitur,
+    OsmumtensFang,
+    DarkBow,
+    ZaryteCrossbow,
+}
+
+/// Bonuses that do not stack with each other. Of the bonuses in a group that apply to an attack,
+/// only the one with the highest priority is used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BonusGroup {
+    /// Salve amulets against undead and black masks on a slayer task
+    UndeadOrSlayer,
 }
 
 impl Attribute {
+    /// The non-stacking group of the attribute's bonus and its priority within the group, lower
+    /// values taking precedence
+    pub fn bonus_group(self) -> Option<(BonusGroup, u8)> {
+        match self {
+            Self::SalveAmuletEnchantedImbued => Some((BonusGroup::UndeadOrSlayer, 0)),
+            Self::SalveAmuletImbued => Some((BonusGroup::UndeadOrSlayer, 1)),
+            Self::SalveAmuletEnchanted => Some((BonusGroup::UndeadOrSlayer, 2)),
+            Self::SalveAmulet => Some((BonusGroup::UndeadOrSlayer, 3)),
+            Self::BlackMaskImbued => Some((BonusGroup::UndeadOrSlayer, 4)),
+            Self::BlackMask => Some((BonusGroup::UndeadOrSlayer, 5)),
+            _ => None,
+        }
+    }
+
+    /// Whether the attribute's bonus applies to an attack against the enemy
+    pub fn bonus_applies(self, player: &Player, enemy: &Enemy) -> bool {
+        match self {
+            Self::SalveAmulet | Self::SalveAmuletEnchanted => salve_amulet_applies(player, enemy),
+            Self::SalveAmuletImbued | Self::SalveAmuletEnchantedImbued => {
+                salve_amulet_imbued_applies(player, enemy)
+            }
+            Self::BlackMask => black_mask_applies(player, enemy),
+            Self::BlackMaskImbued => black_mask_imbued_applies(player, enemy),
+            _ => true,
+        }
+    }
+
     pub fn accuracy_roll_callback(self) -> fn(Scalar, &Player, &Enemy) -> Scalar {
         match self {
             Self::DragonHunterCrossbow => dragon_hunter_crossbow_accuracy,
@@ -94,30 +136,57 @@ impl Attribute {
     }
 }
 
+/// Applies the callbacks of a list of attributes in order, recording each in the trace
 pub trait Callbacks {
-    fn accuracy_roll_callback(&self, value: Scalar, player: &Player, enemy: &Enemy) -> Scalar;
-    fn max_hit_callback(&self, value: Scalar, player: &Player, enemy: &Enemy) -> Scalar;
+    fn accuracy_roll_callback(
+        &self,
+        value: Scalar,
+        player: &Player,
+        enemy: &Enemy,
+        trace: &mut Trace,
+    ) -> Scalar;
+    fn max_hit_callback(
+        &self,
+        value: Scalar,
+        player: &Player,
+        enemy: &Enemy,
+        trace: &mut Trace,
+    ) -> Scalar;
 }
 
 impl Callbacks for Vec<Attribute> {
-    fn accuracy_roll_callback(&self, value: Scalar, player: &Player, enemy: &Enemy) -> Scalar {
+    fn accuracy_roll_callback(
+        &self,
+        value: Scalar,
+        player: &Player,
+        enemy: &Enemy,
+        trace: &mut Trace,
+    ) -> Scalar {
         self.iter().fold(value, |value, attribute| {
-            (attribute.accuracy_roll_callback())(value, player, enemy)
+            let after = (attribute.accuracy_roll_callback())(value, player, enemy);
+            trace.record(StepKind::Callback(*attribute), value, after)
         })
     }
 
-    fn max_hit_callback(&self, value: Scalar, player: &Player, enemy: &Enemy) -> Scalar {
+    fn max_hit_callback(
+        &self,
+        value: Scalar,
+        player: &Player,
+        enemy: &Enemy,
+        trace: &mut Trace,
+    ) -> Scalar {
         self.iter().fold(value, |value, attribute| {
-            (attribute.max_hit_callback())(value, player, enemy)
+            let after = (attribute.max_hit_callback())(value, player, enemy);
+            trace.record(StepKind::Callback(*attribute), value, after)
         })
     }
 }
 
 mod callbacks {
-    use super::Attribute;
     use crate::{
         equipment::combat_styles::StyleType,
         generics::{Fraction, Scalar, Ticks},
+        spells::Spellbook,
         unit::{Enemy, EnemyAttribute, Player},
     };
     use std::cmp::min;
@@ -131,9 +200,7 @@ mod callbacks {
         player: &Player,
         enemy: &Enemy,
     ) -> Scalar {
-        if enemy.has_attribute(&EnemyAttribute::Dragon)
-            && player.combat_option().style_type.is_melee()
-        {
+        if enemy.has_attribute(&EnemyAttribute::Dragon) && player.style_type().is_ranged() {
             value * Fraction::new(13, 10)
         } else {
             value
@@ -145,19 +212,31 @@ mod callbacks {
         player: &Player,
         enemy: &Enemy,
     ) -> Scalar {
-        if enemy.has_attribute(&EnemyAttribute::Dragon)
-            && player.combat_option().style_type.is_ranged()
-        {
+        if enemy.has_attribute(&EnemyAttribute::Dragon) && player.style_type().is_ranged() {
             value * Fraction::new(5, 4)
         } else {
             value
         }
     }
 
+    pub(crate) fn salve_amulet_applies(player: &Player, enemy: &Enemy) -> bool {
+        enemy.has_attribute(&EnemyAttribute::Undead) && player.style_type().is_melee()
+    }
+
+    pub(crate) fn salve_amulet_imbued_applies(_player: &Player, enemy: &Enemy) -> bool {
+        enemy.has_attribute(&EnemyAttribute::Undead)
+    }
+
+    pub(crate) fn black_mask_applies(player: &Player, enemy: &Enemy) -> bool {
+        player.on_slayer_task(enemy) && player.style_type().is_melee()
+    }
+
+    pub(crate) fn black_mask_imbued_applies(player: &Player, enemy: &Enemy) -> bool {
+        player.on_slayer_task(enemy)
+    }
+
     pub(crate) fn salve_amulet(value: Scalar, player: &Player, enemy: &Enemy) -> Scalar {
-        if enemy.has_attribute(&EnemyAttribute::Undead)
-            && player.combat_option().style_type.is_melee()
-        {
+        if salve_amulet_applies(player, enemy) {
             value * Fraction::new(7, 6)
         } else {
             value
@@ -165,70 +244,66 @@ mod callbacks {
     }
 
     pub(crate) fn salve_amulet_enchanted(value: Scalar, player: &Player, enemy: &Enemy) -> Scalar {
-        if enemy.has_attribute(&EnemyAttribute::Undead)
-            && player.combat_option().style_type.is_melee()
-        {
+        if salve_amulet_applies(player, enemy) {
             value * Fraction::new(6, 5)
         } else {
             value
         }
     }
 
-    pub(crate) fn salve_amulet_imbued(value: Scalar, _player: &Player, enemy: &Enemy) -> Scalar {
-        if enemy.has_attribute(&EnemyAttribute::Undead) {
-            value * Fraction::new(7, 6)
-        } else {
-            value
+    pub(crate) fn salve_amulet_imbued(value: Scalar, player: &Player, enemy: &Enemy) -> Scalar {
+        if !salve_amulet_imbued_applies(player, enemy) {
+            return value;
+        }
+
+        match player.style_type() {
+            StyleType::Magic => value * Fraction::new(23, 20),
+            StyleType::None => value,
+            _ => value * Fraction::new(7, 6),
         }
     }
 
     pub(crate) fn salve_amulet_enchanted_imbued(
         value: Scalar,
-        _player: &Player,
+        player: &Player,
         enemy: &Enemy,
     ) -> Scalar {
-        if enemy.has_attribute(&EnemyAttribute::Undead) {
+        if salve_amulet_imbued_applies(player, enemy) {
             value * Fraction::new(6, 5)
         } else {
             value
         }
     }
 
-    pub(crate) fn black_mask(value: Scalar, player: &Player, _enemy: &Enemy) -> Scalar {
-        let attrs = &player.equipped().head.unwrap_or_default().inner.attributes;
-        if player.extra.on_slayer_task
-            && player.combat_option().style_type.is_melee()
-            && !attrs.contains(&Attribute::SalveAmulet)
-            && !attrs.contains(&Attribute::SalveAmuletEnchanted)
-            && !attrs.contains(&Attribute::SalveAmuletImbued)
-            && !attrs.contains(&Attribute::SalveAmuletEnchantedImbued)
-        {
+    pub(crate) fn black_mask(value: Scalar, player: &Player, enemy: &Enemy) -> Scalar {
+        if black_mask_applies(player, enemy) {
             value * Fraction::new(7, 6)
         } else {
             value
         }
     }
 
-    pub(crate) fn black_mask_imbued(value: Scalar, player: &Player, _enemy: &Enemy) -> Scalar {
-        if player.extra.on_slayer_task {
-            let attrs = &player.equipped().head.unwrap_or_default().inner.attributes;
-            match player.combat_option().style_type {
-                StyleType::Stab | StyleType::Slash | StyleType::Crush
-                    if !attrs.contains(&Attribute::SalveAmulet)
-                        && !attrs.contains(&Attribute::SalveAmuletEnchanted)
-                        && !attrs.contains(&Attribute::SalveAmuletImbued)
-                        && !attrs.contains(&Attribute::SalveAmuletEnchantedImbued) =>
-                {
-                    value * Fraction::new(7, 6)
-                }
-                StyleType::Ranged | StyleType::Magic
-                    if !attrs.contains(&Attribute::SalveAmuletImbued)
-                        && !attrs.contains(&Attribute::SalveAmuletEnchantedImbued) =>
-                {
-                    value * Fraction::new(23, 20)
-                }
-                _ => value,
-            }
+    pub(crate) fn black_mask_imbued(value: Scalar, player: &Player, enemy: &Enemy) -> Scalar {
+        if !black_mask_imbued_applies(player, enemy) {
+            return value;
+        }
+
+        match player.style_type() {
+            StyleType::Stab | StyleType::Slash | StyleType::Crush => value * Fraction::new(7, 6),
+            StyleType::Ranged | StyleType::Magic => value * Fraction::new(23, 20),
+            StyleType::None => value,
+        }
+    }
+
+    /// Wilderness weapons are 50% stronger against NPCs in the wilderness while charged. Against
+    /// other players they only have their regular stats.
+    fn wilderness_weapon(value: Scalar, player: &Player, matching_style: bool) -> Scalar {
+        if !player.extra.pvp
+            && matching_style
+            && player.extra.in_wilderness
+            && player.extra.wilderness_weapon_charged
+        {
+            value * Fraction::new(3, 2)
         } else {
             value
         }
@@ -239,11 +314,7 @@ mod callbacks {
         player: &Player,
         _enemy: &Enemy,
     ) -> Scalar {
-        if player.extra.in_wilderness && player.combat_option().style_type.is_melee() {
-            value * Fraction::new(3, 2)
-        } else {
-            value
-        }
+        wilderness_weapon(value, player, player.style_type().is_melee())
     }
 
     pub(crate) fn wilderness_weapon_ranged(
@@ -251,11 +322,7 @@ mod callbacks {
         player: &Player,
         _enemy: &Enemy,
     ) -> Scalar {
-        if player.extra.in_wilderness && player.combat_option().style_type.is_ranged() {
-            value * Fraction::new(3, 2)
-        } else {
-            value
-        }
+        wilderness_weapon(value, player, player.style_type().is_ranged())
     }
 
     pub(crate) fn wilderness_weapon_magic(
@@ -263,17 +330,11 @@ mod callbacks {
         player: &Player,
         _enemy: &Enemy,
     ) -> Scalar {
-        if player.extra.in_wilderness && player.combat_option().style_type.is_magic() {
-            value * Fraction::new(3, 2)
-        } else {
-            value
-        }
+        wilderness_weapon(value, player, player.style_type().is_magic())
     }
 
     pub(crate) fn arclight(value: Scalar, player: &Player, enemy: &Enemy) -> Scalar {
-        if enemy.has_attribute(&EnemyAttribute::Demon)
-            && player.combat_option().style_type.is_melee()
-        {
+        if enemy.has_attribute(&EnemyAttribute::Demon) && player.style_type().is_melee() {
             value * Fraction::new(17, 10)
         } else {
             value
@@ -281,9 +342,7 @@ mod callbacks {
     }
 
     pub(crate) fn blisterwood_accuracy(value: Scalar, player: &Player, enemy: &Enemy) -> Scalar {
-        if enemy.has_attribute(&EnemyAttribute::Vampyre)
-            && player.combat_option().style_type.is_melee()
-        {
+        if enemy.has_attribute(&EnemyAttribute::Vampyre) && player.style_type().is_melee() {
             value * Fraction::new(21, 20)
         } else {
             value
@@ -295,9 +354,7 @@ mod callbacks {
         player: &Player,
         enemy: &Enemy,
     ) -> Scalar {
-        if enemy.has_attribute(&EnemyAttribute::Vampyre)
-            && player.combat_option().style_type.is_melee()
-        {
+        if enemy.has_attribute(&EnemyAttribute::Vampyre) && player.style_type().is_melee() {
             value * Fraction::new(5, 4)
         } else {
             value
@@ -309,9 +366,7 @@ mod callbacks {
         player: &Player,
         enemy: &Enemy,
     ) -> Scalar {
-        if enemy.has_attribute(&EnemyAttribute::Vampyre)
-            && player.combat_option().style_type.is_melee()
-        {
+        if enemy.has_attribute(&EnemyAttribute::Vampyre) && player.style_type().is_melee() {
             value * Fraction::new(23, 20)
         } else {
             value
@@ -319,7 +374,7 @@ mod callbacks {
     }
 
     pub(crate) fn colossal_blade(value: Scalar, player: &Player, enemy: &Enemy) -> Scalar {
-        if player.combat_option().style_type.is_melee() {
+        if player.style_type().is_melee() {
             let size: Scalar = min(enemy.size, 5.into()).into();
             value + (Scalar::new(2) * size)
         } else {
@@ -332,7 +387,11 @@ mod callbacks {
         player: &Player,
         _enemy: &Enemy,
     ) -> Ticks {
-        if player.spell.is_some() {
+        if player.is_casting()
+            && player
+                .spell()
+                .is_some_and(|spell| spell.spellbook == Spellbook::Standard)
+        {
             4.into()
         } else {
             attack_speed
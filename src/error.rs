@@ -0,0 +1,46 @@
+use crate::{
+    equipment::combat_styles::{StyleType, WeaponStyle},
+    spells::Spellbook,
+};
+use derive_more::{Display, Error};
+
+/// Reasons a damage calculation cannot be performed or the player cannot be set up as requested
+#[derive(Debug, Display, Error, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The combat style does not attack, such as a bulwark's Block style
+    #[display(fmt = "The combat style does not attack")]
+    NonAttackingStyle,
+    #[display(fmt = "{style_type:?} cannot be combined with the {weapon_style:?} weapon style")]
+    IncompatibleCombatOption {
+        style_type: StyleType,
+        weapon_style: WeaponStyle,
+    },
+    /// A style specific calculation was requested while attacking with another style type
+    #[display(fmt = "The current combat style attacks with {style_type:?}")]
+    UnexpectedStyleType { style_type: StyleType },
+    /// A magic attack without a spell from a weapon that is not a powered staff
+    #[display(fmt = "No spell is selected and the wielded weapon is not a powered staff")]
+    NoMagicMaxHit,
+    #[display(fmt = "{spell} is not on the {spellbook:?} spellbook")]
+    WrongSpellbook { spell: String, spellbook: Spellbook },
+    #[display(fmt = "{spell} cannot be autocast with the wielded weapon")]
+    CannotAutocast { spell: String },
+    /// An autocast style was selected without a spell the wielded weapon can autocast
+    #[display(fmt = "No spell is selected that the wielded weapon can autocast")]
+    NoAutocastSpell,
+    #[display(fmt = "{item} is not ammunition")]
+    NotAmmunition { item: String },
+    #[display(fmt = "{item} cannot be loaded into the wielded weapon")]
+    CannotLoadAmmunition { item: String },
+    #[display(fmt = "The wielded weapon has no combat style at index {index}")]
+    InvalidCombatStyleIndex { index: usize },
+    #[display(fmt = "No `{name}` style, the options are {}", "options.join(\", \")")]
+    UnknownCombatStyle { name: String, options: Vec<String> },
+    #[display(fmt = "The wielded weapon has no {style_type:?} {weapon_style:?} style")]
+    MissingCombatStyle {
+        style_type: StyleType,
+        weapon_style: WeaponStyle,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
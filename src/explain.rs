@@ -0,0 +1,85 @@
+use crate::{equipment::Attribute, generics::Scalar};
+
+/// What changed the value in a step of an accuracy roll or max hit calculation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StepKind {
+    /// The visible level the calculation starts from
+    Level,
+    Prayer,
+    /// The hidden level boost of the combat style
+    InvisibleBoost,
+    /// The constant added to the boosted level, completing the effective level
+    EffectiveLevel,
+    /// The base max hit of the spell being cast or the powered staff
+    BaseMaxHit,
+    /// The equipment attack or damage bonus applied to the effective level
+    EquipmentBonus,
+    /// The bonus of an item attribute's callback
+    Callback(Attribute),
+    ChinchompaFuse,
+    ElementalWeakness,
+}
+
+/// A single step of a calculation with the value before and after it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Step {
+    pub kind: StepKind,
+    pub before: Scalar,
+    pub after: Scalar,
+}
+
+impl Step {
+    pub fn changed(&self) -> bool {
+        self.before != self.after
+    }
+}
+
+/// Records the steps of a calculation when enabled, and does nothing otherwise
+#[derive(Debug, Clone, Default)]
+pub struct Trace {
+    steps: Option<Vec<Step>>,
+}
+
+impl Trace {
+    pub fn enabled() -> Self {
+        Self {
+            steps: Some(Vec::new()),
+        }
+    }
+
+    pub fn disabled() -> Self {
+        Self { steps: None }
+    }
+
+    /// Records the step and passes its resulting value through
+    pub fn record(&mut self, kind: StepKind, before: Scalar, after: Scalar) -> Scalar {
+        if let Some(steps) = &mut self.steps {
+            steps.push(Step {
+                kind,
+                before,
+                after,
+            });
+        }
+        after
+    }
+
+    pub fn steps(&self) -> &[Step] {
+        self.steps.as_deref().unwrap_or_default()
+    }
+
+    pub fn into_steps(self) -> Vec<Step> {
+        self.steps.unwrap_or_default()
+    }
+}
+
+/// How the player's accuracy roll, max hit and DPS against an enemy were reached
+#[derive(Debug, Clone)]
+pub struct Breakdown {
+    pub accuracy_roll: Scalar,
+    pub accuracy_roll_steps: Vec<Step>,
+    pub max_hit: Scalar,
+    pub max_hit_steps: Vec<Step>,
+    pub enemy_defence_roll: Scalar,
+    pub hit_chance: f64,
+    pub dps: f64,
+}
@@ -0,0 +1,865 @@
+use crate::{
+    damage::HitRangeModifier,
+    equipment::{
+        bolt_effects::BoltProc, combat_styles::WeaponType, AmmunitionClass, AmmunitionRequirement,
+        Slots, SpecialAttack, WeaponStats,
+    },
+    generics::{Fraction, NamedData},
+    prayers::Prayer,
+    spells::Spell,
+    unit::{Enemy, MagicDefence},
+};
+use derive_more::{Display, Error};
+use lazy_static::lazy_static;
+use serde_json::{value::RawValue, Value};
+use std::{collections::HashMap, fmt, path::Path};
+
+pub const EQUIPMENT_FILE: &str = "equipment.json";
+pub const ENEMIES_FILE: &str = "enemies.json";
+pub const PRAYERS_FILE: &str = "prayers.json";
+pub const SPELLS_FILE: &str = "spells.json";
+
+/// Fields only weapons declare, which would otherwise be silently ignored on other slots
+const WEAPON_FIELDS: [&str; 6] = [
+    "weapon_stats",
+    "powered_staff_type",
+    "autocast",
+    "ammunition",
+    "hit_range",
+    "special_attack",
+];
+const AMMUNITION_FIELDS: [&str; 2] = ["projectile", "bolt_proc"];
+
+/// The contents of a data file and the path its problems are reported under
+#[derive(Debug, Clone, Copy)]
+pub struct DataFile<'a> {
+    pub path: &'a str,
+    pub contents: &'a str,
+}
+
+#[derive(Debug, Display, Clone, PartialEq, Eq)]
+pub enum DataErrorKind {
+    #[display(fmt = "could not read the file: {_0}")]
+    Io(String),
+    /// Malformed JSON, unknown enum values, missing fields and similar
+    #[display(fmt = "{_0}")]
+    Parse(String),
+    #[display(fmt = "duplicate entry, first defined on line {first_line}")]
+    Duplicate { first_line: usize },
+    /// Entries that parse but combine fields in a way the calculations cannot use
+    #[display(fmt = "{_0}")]
+    Invalid(String),
+    /// Overrides that cannot be merged into an entry
+    #[display(fmt = "conflicting override: {_0}")]
+    Conflict(String),
+}
+
+/// A problem with a single entry, or a whole file if `name` is unknown
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataError {
+    pub file: String,
+    pub line: Option<usize>,
+    pub name: Option<String>,
+    pub kind: DataErrorKind,
+}
+
+impl fmt::Display for DataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file)?;
+        if let Some(line) = self.line {
+            write!(f, ":{line}")?;
+        }
+        if let Some(name) = &self.name {
+            write!(f, " ({name})")?;
+        }
+        write!(f, ": {}", self.kind)
+    }
+}
+
+impl std::error::Error for DataError {}
+
+/// Every problem found while loading the data files
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub struct LoadError {
+    pub errors: Vec<DataError>,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let errors: Vec<String> = self.errors.iter().map(ToString::to_string).collect();
+        write!(f, "{}", errors.join("\n"))
+    }
+}
+
+/// A lookup of a name that is not in the data, with the closest known name if one is similar
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub struct UnknownName {
+    pub kind: &'static str,
+    pub name: String,
+    pub suggestion: Option<String>,
+}
+
+impl fmt::Display for UnknownName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown {} `{}`", self.kind, self.name)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, ", did you mean `{suggestion}`?")?;
+        }
+        Ok(())
+    }
+}
+
+/// Where an entry was defined, for reporting problems with it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Origin {
+    pub file: String,
+    pub line: usize,
+}
+
+/// The entries of one kind of data by name, with where each was defined
+#[derive(Debug, Clone)]
+struct Entries<T> {
+    by_name: HashMap<String, T>,
+    origins: HashMap<String, Origin>,
+    /// The JSON each entry was read from, which overrides are merged into
+    raw: HashMap<String, Value>,
+}
+
+impl<T> Default for Entries<T> {
+    fn default() -> Self {
+        Self {
+            by_name: HashMap::new(),
+            origins: HashMap::new(),
+            raw: HashMap::new(),
+        }
+    }
+}
+
+impl<T: NamedData> Entries<T> {
+    /// Adds the entries, replacing existing entries with the same name
+    fn layer(&mut self, entries: Self) {
+        self.by_name.extend(entries.by_name);
+        self.origins.extend(entries.origins);
+        self.raw.extend(entries.raw);
+    }
+
+    fn origin(&self, name: &str) -> Option<&Origin> {
+        self.origins.get(name)
+    }
+
+    fn insert(&mut self, name: String, entry: T, raw: Value, origin: Origin) {
+        self.origins.insert(name.clone(), origin);
+        self.raw.insert(name.clone(), raw);
+        self.by_name.insert(name, entry);
+    }
+
+    /// Merges each entry of the file into the existing entry of the same name field by field, or
+    /// adds it if there is none
+    fn patch(
+        &mut self,
+        file: DataFile,
+        check: fn(&Value) -> Option<String>,
+        errors: &mut Vec<DataError>,
+    ) {
+        let mut patches: HashMap<String, (usize, Value)> = HashMap::new();
+        for (line, patch) in raw_entries(file, errors) {
+            let mut error = |name: Option<String>, kind| {
+                errors.push(DataError {
+                    file: file.path.to_owned(),
+                    line: Some(line),
+                    name,
+                    kind,
+                });
+            };
+            let Some(name) = patch.get("name").and_then(Value::as_str).map(str::to_owned) else {
+                error(
+                    None,
+                    DataErrorKind::Parse("entry without a `name`".to_owned()),
+                );
+                continue;
+            };
+
+            let mut conflicts: Vec<String> = Vec::new();
+            if let Some((first_line, first_patch)) = patches.get(&name) {
+                conflicts.extend(
+                    conflicting_fields(first_patch, &patch, "")
+                        .into_iter()
+                        .map(|field| {
+                            format!("`{field}` is already overridden on line {first_line}")
+                        }),
+                );
+            }
+            let merged = match self.raw.get(&name) {
+                Some(base) => {
+                    let mut type_changes = Vec::new();
+                    let merged = merge(base, &patch, "", &mut type_changes);
+                    conflicts.extend(type_changes.into_iter().map(|field| {
+                        format!("`{field}` cannot change between an object and a value")
+                    }));
+                    merged
+                }
+                None => patch.clone(),
+            };
+            if !conflicts.is_empty() {
+                for conflict in conflicts {
+                    error(Some(name.clone()), DataErrorKind::Conflict(conflict));
+                }
+                continue;
+            }
+            if let Some(reason) = check(&merged) {
+                error(Some(name), DataErrorKind::Invalid(reason));
+                continue;
+            }
+
+            match T::deserialize(&merged) {
+                Ok(entry) => {
+                    let origin = Origin {
+                        file: file.path.to_owned(),
+                        line,
+                    };
+                    self.insert(name.clone(), entry, merged, origin);
+                    patches.insert(name, (line, patch));
+                }
+                Err(parse_error) => error(
+                    Some(name),
+                    DataErrorKind::Parse(without_position(&parse_error)),
+                ),
+            }
+        }
+    }
+}
+
+/// Data files to layer on top of existing data. Kinds of data without a file keep their entries.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DataFiles<'a> {
+    pub equipment: Option<DataFile<'a>>,
+    pub enemies: Option<DataFile<'a>>,
+    pub prayers: Option<DataFile<'a>>,
+    pub spells: Option<DataFile<'a>>,
+}
+
+lazy_static! {
+    static ref BUNDLED: GameData = GameData::from_files(
+        DataFile {
+            path: "data/equipment.json",
+            contents: include_str!("../data/equipment.json"),
+        },
+        DataFile {
+            path: "data/enemies.json",
+            contents: include_str!("../data/enemies.json"),
+        },
+        DataFile {
+            path: "data/prayers.json",
+            contents: include_str!("../data/prayers.json"),
+        },
+        DataFile {
+            path: "data/spells.json",
+            contents: include_str!("../data/spells.json"),
+        },
+    )
+    .expect("Bundled data is valid");
+}
+
+/// All equipment, enemies, prayers and spells, checked against each other
+#[derive(Debug, Clone, Default)]
+pub struct GameData {
+    items: Entries<Slots>,
+    enemies: Entries<Enemy>,
+    prayers: Entries<Prayer>,
+    spells: Entries<Spell>,
+}
+
+impl GameData {
+    /// The data shipped with the crate, compiled into the binary
+    ///
+    /// # Panics
+    /// Panics if the bundled data is invalid, which the crate's tests rule out
+    pub fn bundled() -> &'static Self {
+        &BUNDLED
+    }
+
+    /// Loads the equipment, enemies, prayers and spells files from the directory
+    ///
+    /// # Errors
+    /// Returns every problem found in any of the files
+    pub fn load(directory: impl AsRef<Path>) -> Result<Self, LoadError> {
+        let directory = directory.as_ref();
+        let mut errors = Vec::new();
+        let [equipment, enemies, prayers, spells] =
+            [EQUIPMENT_FILE, ENEMIES_FILE, PRAYERS_FILE, SPELLS_FILE].map(|file_name| {
+                read_data_file(&directory.join(file_name), false, &mut errors)
+                    // An empty list keeps the other files' problems reportable
+                    .unwrap_or_else(|| (file_name.to_owned(), "[]".to_owned()))
+            });
+
+        let data = Self::from_files(
+            DataFile {
+                path: &equipment.0,
+                contents: &equipment.1,
+            },
+            DataFile {
+                path: &enemies.0,
+                contents: &enemies.1,
+            },
+            DataFile {
+                path: &prayers.0,
+                contents: &prayers.1,
+            },
+            DataFile {
+                path: &spells.0,
+                contents: &spells.1,
+            },
+        );
+
+        with_errors(data, errors)
+    }
+
+    /// Parses and validates the contents of the four data files
+    ///
+    /// # Errors
+    /// Returns every problem found in any of the files
+    pub fn from_files(
+        equipment: DataFile,
+        enemies: DataFile,
+        prayers: DataFile,
+        spells: DataFile,
+    ) -> Result<Self, LoadError> {
+        Self::default().layered(DataFiles {
+            equipment: Some(equipment),
+            enemies: Some(enemies),
+            prayers: Some(prayers),
+            spells: Some(spells),
+        })
+    }
+
+    /// A copy of the data with the entries of the files added on top. Entries with the name of an
+    /// existing entry replace it entirely. The data itself is left untouched.
+    ///
+    /// # Errors
+    /// Returns every problem found in the files, or in the combined data
+    pub fn layered(&self, files: DataFiles) -> Result<Self, LoadError> {
+        let mut errors = Vec::new();
+        let mut data = self.clone();
+        if let Some(file) = files.equipment {
+            data.items
+                .layer(parse_entries(file, check_equipment, &mut errors));
+        }
+        if let Some(file) = files.enemies {
+            data.enemies
+                .layer(parse_entries(file, |_| None, &mut errors));
+        }
+        if let Some(file) = files.prayers {
+            data.prayers
+                .layer(parse_entries(file, |_| None, &mut errors));
+        }
+        if let Some(file) = files.spells {
+            data.spells
+                .layer(parse_entries(file, |_| None, &mut errors));
+        }
+        data.validated(errors)
+    }
+
+    /// A copy of the data with the entries of the files merged in field by field. Fields missing
+    /// from an override keep their existing values, nested objects are merged and any other value
+    /// is replaced. Entries with new names are added and must be complete. The data itself is left
+    /// untouched.
+    ///
+    /// # Errors
+    /// Returns every problem found in the files, conflicting overrides, and problems in the merged
+    /// data
+    pub fn with_overrides(&self, files: DataFiles) -> Result<Self, LoadError> {
+        let mut errors = Vec::new();
+        let mut data = self.clone();
+        if let Some(file) = files.equipment {
+            data.items.patch(file, check_equipment, &mut errors);
+        }
+        if let Some(file) = files.enemies {
+            data.enemies.patch(file, |_| None, &mut errors);
+        }
+        if let Some(file) = files.prayers {
+            data.prayers.patch(file, |_| None, &mut errors);
+        }
+        if let Some(file) = files.spells {
+            data.spells.patch(file, |_| None, &mut errors);
+        }
+        data.validated(errors)
+    }
+
+    /// A copy of the data with those of the equipment, enemies, prayers and spells files that exist
+    /// in the directory merged in, as with `with_overrides`
+    ///
+    /// # Errors
+    /// Returns every problem found in the files, conflicting overrides, and problems in the merged
+    /// data
+    pub fn with_overrides_from(&self, directory: impl AsRef<Path>) -> Result<Self, LoadError> {
+        let mut errors = Vec::new();
+        let [equipment, enemies, prayers, spells] =
+            read_optional_files(directory.as_ref(), &mut errors);
+        let data = self.with_overrides(DataFiles {
+            equipment: as_data_file(equipment.as_ref()),
+            enemies: as_data_file(enemies.as_ref()),
+            prayers: as_data_file(prayers.as_ref()),
+            spells: as_data_file(spells.as_ref()),
+        });
+
+        with_errors(data, errors)
+    }
+
+    /// The data if there are no errors, including problems found validating it
+    fn validated(self, mut errors: Vec<DataError>) -> Result<Self, LoadError> {
+        errors.extend(self.validate());
+
+        if errors.is_empty() {
+            Ok(self)
+        } else {
+            errors.sort_by(|lhs, rhs| (&lhs.file, lhs.line).cmp(&(&rhs.file, rhs.line)));
+            Err(LoadError { errors })
+        }
+    }
+
+    /// A copy of the data with those of the equipment, enemies, prayers and spells files that exist
+    /// in the directory layered on top, as with `layered`
+    ///
+    /// # Errors
+    /// Returns every problem found in the files, or in the combined data
+    pub fn layered_from(&self, directory: impl AsRef<Path>) -> Result<Self, LoadError> {
+        let mut errors = Vec::new();
+        let [equipment, enemies, prayers, spells] =
+            read_optional_files(directory.as_ref(), &mut errors);
+        let data = self.layered(DataFiles {
+            equipment: as_data_file(equipment.as_ref()),
+            enemies: as_data_file(enemies.as_ref()),
+            prayers: as_data_file(prayers.as_ref()),
+            spells: as_data_file(spells.as_ref()),
+        });
+
+        with_errors(data, errors)
+    }
+
+    fn validate(&self) -> Vec<DataError> {
+        let mut errors = Vec::new();
+        let mut invalid = |origin: Option<&Origin>, name: &str, reason| {
+            errors.push(DataError {
+                file: origin.map(|origin| origin.file.clone()).unwrap_or_default(),
+                line: origin.map(|origin| origin.line),
+                name: Some(name.to_owned()),
+                kind: DataErrorKind::Invalid(reason),
+            });
+        };
+        for (name, item) in sorted(&self.items.by_name) {
+            for reason in self.item_problems(item) {
+                invalid(self.items.origin(name), name, reason);
+            }
+        }
+        for (name, enemy) in sorted(&self.enemies.by_name) {
+            for reason in enemy_problems(enemy) {
+                invalid(self.enemies.origin(name), name, reason);
+            }
+        }
+        errors
+    }
+
+    /// Where the named item was defined
+    pub fn item_origin(&self, name: &str) -> Option<&Origin> {
+        self.items.origin(name)
+    }
+
+    /// Where the named enemy was defined
+    pub fn enemy_origin(&self, name: &str) -> Option<&Origin> {
+        self.enemies.origin(name)
+    }
+
+    pub fn items(&self) -> &HashMap<String, Slots> {
+        &self.items.by_name
+    }
+
+    pub fn enemies(&self) -> &HashMap<String, Enemy> {
+        &self.enemies.by_name
+    }
+
+    pub fn prayers(&self) -> &HashMap<String, Prayer> {
+        &self.prayers.by_name
+    }
+
+    pub fn spells(&self) -> &HashMap<String, Spell> {
+        &self.spells.by_name
+    }
+
+    /// # Errors
+    /// Returns an error suggesting the closest item name if no item has the given name
+    pub fn item(&self, name: &str) -> Result<&Slots, UnknownName> {
+        lookup(&self.items.by_name, "item", name)
+    }
+
+    /// # Errors
+    /// Returns an error suggesting the closest enemy name if no enemy has the given name
+    pub fn enemy(&self, name: &str) -> Result<&Enemy, UnknownName> {
+        lookup(&self.enemies.by_name, "enemy", name)
+    }
+
+    /// # Errors
+    /// Returns an error suggesting the closest prayer name if no prayer has the given name
+    pub fn prayer(&self, name: &str) -> Result<&Prayer, UnknownName> {
+        lookup(&self.prayers.by_name, "prayer", name)
+    }
+
+    /// # Errors
+    /// Returns an error suggesting the closest spell name if no spell has the given name
+    pub fn spell(&self, name: &str) -> Result<&Spell, UnknownName> {
+        lookup(&self.spells.by_name, "spell", name)
+    }
+
+    fn item_problems(&self, item: &Slots) -> Vec<String> {
+        match item {
+            Slots::WeaponOneHanded(weapon) => self.weapon_problems(
+                weapon.weapon_stats,
+                weapon.ammunition.as_ref(),
+                weapon.hit_range.as_ref(),
+                weapon.special_attack.as_ref(),
+            ),
+            Slots::WeaponTwoHanded(weapon) => self.weapon_problems(
+                weapon.weapon_stats,
+                weapon.ammunition.as_ref(),
+                weapon.hit_range.as_ref(),
+                weapon.special_attack.as_ref(),
+            ),
+            Slots::Ammunition(ammunition) => bolt_proc_problems(
+                ammunition.bolt_proc.as_ref(),
+                ammunition.projectile.map(|projectile| projectile.class),
+            ),
+            _ => Vec::new(),
+        }
+    }
+
+    fn weapon_problems(
+        &self,
+        weapon_stats: WeaponStats,
+        ammunition: Option<&AmmunitionRequirement>,
+        hit_range: Option<&HitRangeModifier>,
+        special_attack: Option<&SpecialAttack>,
+    ) -> Vec<String> {
+        let mut problems = Vec::new();
+        if i32::from(weapon_stats.attack_speed) <= 0 {
+            problems.push("`attack_speed` must be positive".to_owned());
+        }
+        for combat_option in weapon_stats.weapon_type.combat_boost() {
+            if let Err(error) = combat_option.invisible_boost() {
+                problems.push(format!("combat style {}: {error}", combat_option.name));
+            }
+        }
+        if ammunition.is_some()
+            && !matches!(
+                weapon_stats.weapon_type,
+                WeaponType::Bow | WeaponType::Crossbow | WeaponType::Thrown
+            )
+        {
+            problems.push(format!(
+                "`ammunition` on a {:?} weapon, which does not fire ammunition",
+                weapon_stats.weapon_type
+            ));
+        }
+
+        let hit_ranges = hit_range
+            .into_iter()
+            .chain(special_attack.into_iter().flat_map(|special_attack| {
+                std::iter::once(&special_attack.hit_range).chain(special_attack.ammunition.values())
+            }));
+        if hit_ranges
+            .filter_map(|hit_range| hit_range.damage.as_ref())
+            .any(|damage| !valid_fraction(damage))
+        {
+            problems.push("hit range `damage` has a zero divisor".to_owned());
+        }
+        if let Some(special_attack) = special_attack {
+            let mut names: Vec<&String> = special_attack.ammunition.keys().collect();
+            names.sort();
+            for name in names {
+                if !matches!(self.items.by_name.get(name), Some(Slots::Ammunition(_))) {
+                    problems.push(format!(
+                        "special attack refers to `{name}`, which is not an ammunition item"
+                    ));
+                }
+            }
+        }
+
+        problems
+    }
+}
+
+fn bolt_proc_problems(bolt_proc: Option<&BoltProc>, class: Option<AmmunitionClass>) -> Vec<String> {
+    let mut problems = Vec::new();
+    if let Some(bolt_proc) = bolt_proc {
+        if !valid_fraction(&bolt_proc.chance) {
+            problems.push("bolt effect `chance` has a zero divisor".to_owned());
+        }
+        if class != Some(AmmunitionClass::Bolt) {
+            problems.push("bolt effect on ammunition that is not a bolt".to_owned());
+        }
+    }
+    problems
+}
+
+fn enemy_problems(enemy: &Enemy) -> Vec<String> {
+    let mut problems = Vec::new();
+    if *crate::generics::Scalar::from(enemy.size) < 1 {
+        problems.push("`size` must be at least 1".to_owned());
+    }
+    if let MagicDefence::Blend { magic, defence } = enemy.magic_defence {
+        if !valid_fraction(&magic) || !valid_fraction(&defence) {
+            problems.push("`magic_defence` blend has a zero divisor".to_owned());
+        }
+    }
+    problems
+}
+
+fn valid_fraction(fraction: &Fraction) -> bool {
+    fraction.divisor != 0
+}
+
+/// Catches fields that deserialization would silently ignore or report without naming the slot
+fn check_equipment(value: &Value) -> Option<String> {
+    let slot = value.get("slot").and_then(Value::as_str)?;
+    let is_weapon = matches!(slot, "WeaponOneHanded" | "WeaponTwoHanded");
+    if is_weapon && value.get("weapon_stats").is_none() {
+        return Some("weapon without `weapon_stats`".to_owned());
+    }
+
+    let misplaced = |fields: &[&'static str], valid: bool| {
+        fields
+            .iter()
+            .find(|field| !valid && value.get(field).is_some())
+            .copied()
+    };
+    misplaced(&WEAPON_FIELDS, is_weapon)
+        .or_else(|| misplaced(&AMMUNITION_FIELDS, slot == "Ammunition"))
+        .map(|field| format!("`{field}` is not valid in the {slot} slot"))
+}
+
+/// Reads a data file, returning its path as displayed in errors and its contents. Missing files
+/// are skipped without an error if they are `optional`.
+fn read_data_file(
+    path: &Path,
+    optional: bool,
+    errors: &mut Vec<DataError>,
+) -> Option<(String, String)> {
+    let display_path = path.display().to_string();
+    match std::fs::read_to_string(path) {
+        Ok(contents) => Some((display_path, contents)),
+        Err(error) if optional && error.kind() == std::io::ErrorKind::NotFound => None,
+        Err(error) => {
+            errors.push(DataError {
+                file: display_path,
+                line: None,
+                name: None,
+                kind: DataErrorKind::Io(error.to_string()),
+            });
+            None
+        }
+    }
+}
+
+/// Reads those of the equipment, enemies, prayers and spells files that exist in the directory
+fn read_optional_files(
+    directory: &Path,
+    errors: &mut Vec<DataError>,
+) -> [Option<(String, String)>; 4] {
+    [EQUIPMENT_FILE, ENEMIES_FILE, PRAYERS_FILE, SPELLS_FILE]
+        .map(|file_name| read_data_file(&directory.join(file_name), true, errors))
+}
+
+fn as_data_file(file: Option<&(String, String)>) -> Option<DataFile<'_>> {
+    file.map(|(path, contents)| DataFile { path, contents })
+}
+
+/// Adds the errors of reading files to the result of loading them
+fn with_errors(
+    data: Result<GameData, LoadError>,
+    mut errors: Vec<DataError>,
+) -> Result<GameData, LoadError> {
+    match data {
+        Ok(data) if errors.is_empty() => Ok(data),
+        Ok(_) => Err(LoadError { errors }),
+        Err(load_error) => {
+            errors.extend(load_error.errors);
+            Err(LoadError { errors })
+        }
+    }
+}
+
+/// The entries of a file as JSON with the line each starts on
+fn raw_entries(file: DataFile, errors: &mut Vec<DataError>) -> Vec<(usize, Value)> {
+    match serde_json::from_str::<Vec<&RawValue>>(file.contents) {
+        Ok(raw_entries) => raw_entries
+            .into_iter()
+            .map(|raw_entry| {
+                let line = line_of(file.contents, raw_entry.get());
+                (
+                    line,
+                    serde_json::from_str(raw_entry.get()).unwrap_or_default(),
+                )
+            })
+            .collect(),
+        Err(parse_error) => {
+            errors.push(DataError {
+                file: file.path.to_owned(),
+                line: Some(parse_error.line()),
+                name: None,
+                kind: DataErrorKind::Parse(without_position(&parse_error)),
+            });
+            Vec::new()
+        }
+    }
+}
+
+/// Parses every entry of a file on its own, so that one broken entry does not hide problems in
+/// the others.
+fn parse_entries<T: NamedData>(
+    file: DataFile,
+    check: fn(&Value) -> Option<String>,
+    errors: &mut Vec<DataError>,
+) -> Entries<T> {
+    let mut entries = Entries::default();
+    for (line, value) in raw_entries(file, errors) {
+        let mut error = |name: Option<String>, kind| {
+            errors.push(DataError {
+                file: file.path.to_owned(),
+                line: Some(line),
+                name,
+                kind,
+            });
+        };
+        let name = value.get("name").and_then(Value::as_str).map(str::to_owned);
+
+        if let Some(reason) = check(&value) {
+            error(name, DataErrorKind::Invalid(reason));
+            continue;
+        }
+
+        match T::deserialize(&value) {
+            Ok(entry) => {
+                let name = entry.get_name().to_owned();
+                if let Some(first) = entries.origin(&name) {
+                    let first_line = first.line;
+                    error(Some(name), DataErrorKind::Duplicate { first_line });
+                } else {
+                    let origin = Origin {
+                        file: file.path.to_owned(),
+                        line,
+                    };
+                    entries.insert(name, entry, value, origin);
+                }
+            }
+            Err(parse_error) => error(name, DataErrorKind::Parse(without_position(&parse_error))),
+        }
+    }
+
+    entries
+}
+
+/// Merges `patch` into `base` field by field, replacing everything but objects. Fields that would
+/// change between an object and another value are recorded in `conflicts` and keep the base value.
+fn merge(base: &Value, patch: &Value, path: &str, conflicts: &mut Vec<String>) -> Value {
+    match (base, patch) {
+        (Value::Object(base_fields), Value::Object(patch_fields)) => {
+            let mut merged = base_fields.clone();
+            for (key, value) in patch_fields {
+                let field = field_path(path, key);
+                let merged_value = match base_fields.get(key) {
+                    Some(base_value) => merge(base_value, value, &field, conflicts),
+                    None => value.clone(),
+                };
+                merged.insert(key.clone(), merged_value);
+            }
+            Value::Object(merged)
+        }
+        (Value::Object(_), _) | (_, Value::Object(_)) if !base.is_null() && !patch.is_null() => {
+            conflicts.push(path.to_owned());
+            base.clone()
+        }
+        _ => patch.clone(),
+    }
+}
+
+/// Fields that both patches set to different values
+fn conflicting_fields(first: &Value, second: &Value, path: &str) -> Vec<String> {
+    match (first, second) {
+        (Value::Object(first_fields), Value::Object(second_fields)) => first_fields
+            .iter()
+            .filter_map(|(key, first_value)| {
+                second_fields.get(key).map(|second_value| {
+                    conflicting_fields(first_value, second_value, &field_path(path, key))
+                })
+            })
+            .flatten()
+            .collect(),
+        _ if first != second => vec![path.to_owned()],
+        _ => Vec::new(),
+    }
+}
+
+fn field_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+/// The line `entry`, a slice of `contents`, starts on
+fn line_of(contents: &str, entry: &str) -> usize {
+    let offset = (entry.as_ptr() as usize).saturating_sub(contents.as_ptr() as usize);
+    contents[..offset.min(contents.len())].matches('\n').count() + 1
+}
+
+/// The error message without serde's position, which is relative to the entry rather than the file
+fn without_position(error: &serde_json::Error) -> String {
+    let message = error.to_string();
+    match message.rfind(" at line ") {
+        Some(index) if error.line() > 0 => message[..index].to_owned(),
+        _ => message,
+    }
+}
+
+fn sorted<T>(map: &HashMap<String, T>) -> Vec<(&String, &T)> {
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_by_key(|(name, _)| *name);
+    entries
+}
+
+fn lookup<'a, T>(
+    map: &'a HashMap<String, T>,
+    kind: &'static str,
+    name: &str,
+) -> Result<&'a T, UnknownName> {
+    map.get(name).ok_or_else(|| UnknownName {
+        kind,
+        name: name.to_owned(),
+        suggestion: closest_name(map.keys(), name),
+    })
+}
+
+/// The known name with the smallest edit distance to `name`, ignoring case, if it is close enough
+/// to be a likely typo
+fn closest_name<'a>(names: impl Iterator<Item = &'a String>, name: &str) -> Option<String> {
+    let name = name.to_lowercase();
+    let max_distance = (name.chars().count() / 3).max(2);
+    names
+        .map(|candidate| (edit_distance(&candidate.to_lowercase(), &name), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate.clone())
+}
+
+fn edit_distance(lhs: &str, rhs: &str) -> usize {
+    let rhs: Vec<char> = rhs.chars().collect();
+    let mut previous: Vec<usize> = (0..=rhs.len()).collect();
+    for (i, lhs_char) in lhs.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, rhs_char) in rhs.iter().enumerate() {
+            let substitution = previous[j] + usize::from(lhs_char != *rhs_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[rhs.len()]
+}
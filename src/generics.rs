@@ -1,14 +1,21 @@
 use anyhow::Result;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 pub const SECONDS_PER_TICK: f64 = 0.6;
+pub const MAX_ATTACK_RANGE: i32 = 10;
+
+/// Converts a count or amount of GP for floating point arithmetic, rounding values beyond 2^53
+#[allow(clippy::cast_precision_loss)]
+pub fn to_f64(value: u64) -> f64 {
+    value as f64
+}
 
 pub trait NamedData: for<'a> Deserialize<'a> {
     fn get_name(&self) -> &str;
 }
 
-#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct Fraction {
     pub dividend: i32,
     pub divisor: i32,
@@ -42,6 +49,12 @@ impl std::ops::Mul<Scalar> for Fraction {
 )]
 pub struct Percentage(i32);
 
+impl From<Percentage> for Fraction {
+    fn from(value: Percentage) -> Self {
+        Self::new(value.0, 100)
+    }
+}
+
 impl std::ops::Mul<Scalar> for Percentage {
     type Output = Scalar;
 
@@ -52,6 +65,7 @@ impl std::ops::Mul<Scalar> for Percentage {
 
 #[derive(
     Deserialize,
+    Serialize,
     Debug,
     Clone,
     Copy,
@@ -110,6 +124,7 @@ impl From<Tiles> for Scalar {
 
 #[derive(
     Deserialize,
+    Serialize,
     Debug,
     Clone,
     Copy,
@@ -1,5 +1,12 @@
+pub mod bank;
+pub mod damage;
 pub mod equipment;
+pub mod error;
+pub mod explain;
+pub mod game_data;
 pub mod generics;
+pub mod loadout;
+pub mod optimizer;
 pub mod prayers;
 pub mod spells;
 pub mod unit;
@@ -0,0 +1,281 @@
+use derive_more::{Display, Error};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    equipment::{combat_styles::WeaponStyle, ContainsEquipment, Slot, Slots, Wielded},
+    error::Error,
+    game_data::{GameData, UnknownName},
+    generics::{NamedData, Scalar},
+    spells::{CastMode, Spellbook},
+    unit::{Extra, Levels, Player, SlayerCategory},
+};
+
+/// A player setup that refers to items, prayers and spells by name, so that it can be saved and
+/// shared as JSON and later resolved against the game data into a `Player`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Loadout {
+    pub levels: Levels,
+    /// Temporary changes to the levels, such as from potions
+    pub boosts: Boosts,
+    pub gear: Gear,
+    pub prayers: Vec<String>,
+    pub spellbook: Spellbook,
+    pub spell: Option<String>,
+    pub casting: Casting,
+    /// The name of the combat style, the weapon's first style if missing. Ignored when autocasting
+    pub style: Option<String>,
+    pub special_attack: bool,
+    pub slayer_task: Option<SlayerCategory>,
+    pub extra: Extra,
+}
+
+/// Item names per equipment slot
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Gear {
+    pub head: Option<String>,
+    pub cape: Option<String>,
+    pub neck: Option<String>,
+    pub ammunition: Option<String>,
+    /// Ammunition loaded into a weapon that holds its own, like darts into a blowpipe
+    pub loaded_ammunition: Option<String>,
+    /// A one or two handed weapon
+    pub weapon: Option<String>,
+    pub shield: Option<String>,
+    pub body: Option<String>,
+    pub legs: Option<String>,
+    pub hands: Option<String>,
+    pub feet: Option<String>,
+    pub ring: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Boosts {
+    pub hitpoints: Scalar,
+    pub attack: Scalar,
+    pub strength: Scalar,
+    pub defence: Scalar,
+    pub ranged: Scalar,
+    pub magic: Scalar,
+    pub prayer: Scalar,
+}
+
+/// How the loadout's spell is cast
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Casting {
+    #[default]
+    Manual,
+    Autocast,
+    DefensiveAutocast,
+}
+
+/// Reasons a loadout cannot be resolved into a player
+#[derive(Debug, Display, Error, Clone, PartialEq, Eq)]
+pub enum LoadoutError {
+    #[display(fmt = "{_0}")]
+    UnknownName(#[error(source)] UnknownName),
+    /// An item named in a slot it cannot be worn in
+    #[display(fmt = "{item} cannot be worn in the {slot} slot")]
+    WrongSlot { item: String, slot: Slot },
+    #[display(fmt = "{weapon} is two handed and cannot be wielded with {shield}")]
+    TwoHandedWithShield { weapon: String, shield: String },
+    #[display(
+        fmt = "{weapon} has no `{style}` style, the options are {}",
+        "options.join(\", \")"
+    )]
+    UnknownStyle {
+        style: String,
+        weapon: String,
+        options: Vec<String>,
+    },
+    /// The spell or loaded ammunition cannot be used with the rest of the loadout
+    #[display(fmt = "{_0}")]
+    Incompatible(#[error(source)] Error),
+}
+
+impl From<UnknownName> for LoadoutError {
+    fn from(value: UnknownName) -> Self {
+        Self::UnknownName(value)
+    }
+}
+
+impl Loadout {
+    /// Looks up every name in the data and builds the player
+    ///
+    /// # Errors
+    /// Returns an error if a name is unknown, an item is in the wrong slot, a shield is worn with a
+    /// two handed weapon, or the style, spell or loaded ammunition do not fit the weapon
+    pub fn resolve<'a>(&self, data: &'a GameData) -> Result<Player<'a>, LoadoutError> {
+        let mut player = Player::default()
+            .set_levels(self.boosted_levels())
+            .set_spellbook(self.spellbook)
+            .assign_slayer_task(self.slayer_task)
+            .use_special_attack(self.special_attack);
+        player.extra = self.extra;
+
+        let gear = &self.gear;
+        let slots = [
+            (Slot::Head, &gear.head),
+            (Slot::Cape, &gear.cape),
+            (Slot::Neck, &gear.neck),
+            (Slot::Ammunition, &gear.ammunition),
+            (Slot::Weapon, &gear.weapon),
+            (Slot::Shield, &gear.shield),
+            (Slot::Body, &gear.body),
+            (Slot::Legs, &gear.legs),
+            (Slot::Hands, &gear.hands),
+            (Slot::Feet, &gear.feet),
+            (Slot::Ring, &gear.ring),
+        ];
+        for (slot, name) in slots {
+            if let Some(name) = name {
+                let item = data.item(name)?;
+                if item.slot() != slot {
+                    return Err(LoadoutError::WrongSlot {
+                        item: name.clone(),
+                        slot,
+                    });
+                }
+                if let (
+                    Slots::Shield(_),
+                    Wielded::TwoHanded {
+                        weapon: Some(weapon),
+                    },
+                ) = (item, player.equipped().wielded)
+                {
+                    return Err(LoadoutError::TwoHandedWithShield {
+                        weapon: weapon.inner.name.clone(),
+                        shield: name.clone(),
+                    });
+                }
+                player = player.equip(item);
+            }
+        }
+        if let Some(name) = &gear.loaded_ammunition {
+            player = player
+                .load_ammunition(data.item(name)?)
+                .map_err(LoadoutError::Incompatible)?;
+        }
+
+        for name in &self.prayers {
+            player = player.activate_prayer(data.prayer(name)?);
+        }
+
+        if let Some(style) = self
+            .style
+            .as_ref()
+            .filter(|_| self.casting == Casting::Manual)
+        {
+            let weapon = weapon_name(&player.equipped().wielded)
+                .unwrap_or("Unarmed")
+                .to_owned();
+            player
+                .select_combat_style(style)
+                .map_err(|error| match error {
+                    Error::UnknownCombatStyle { name, options } => LoadoutError::UnknownStyle {
+                        style: name,
+                        weapon,
+                        options,
+                    },
+                    error => LoadoutError::Incompatible(error),
+                })?;
+        }
+
+        if let Some(name) = &self.spell {
+            let spell = data.spell(name)?;
+            player = match self.casting {
+                Casting::Manual => player.select_spell(spell),
+                Casting::Autocast => player.autocast_spell(spell, false),
+                Casting::DefensiveAutocast => player.autocast_spell(spell, true),
+            }
+            .map_err(LoadoutError::Incompatible)?;
+        }
+
+        Ok(player)
+    }
+
+    fn boosted_levels(&self) -> Levels {
+        let (levels, boosts) = (self.levels, self.boosts);
+        Levels {
+            hitpoints: levels.hitpoints + boosts.hitpoints,
+            attack: levels.attack + boosts.attack,
+            strength: levels.strength + boosts.strength,
+            defence: levels.defence + boosts.defence,
+            ranged: levels.ranged + boosts.ranged,
+            magic: levels.magic + boosts.magic,
+            prayer: levels.prayer + boosts.prayer,
+        }
+    }
+
+    /// # Errors
+    /// Returns an error if the JSON is malformed
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    /// # Errors
+    /// Returns an error if the loadout cannot be serialized
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+impl From<&Player<'_>> for Loadout {
+    /// The loadout of the player, with its current levels and no boosts
+    fn from(player: &Player<'_>) -> Self {
+        let equipped = player.equipped();
+        let shield = match equipped.wielded {
+            Wielded::OneHanded { shield, .. } => shield.map(|shield| shield.inner.name.clone()),
+            Wielded::TwoHanded { .. } => None,
+        };
+        let autocasting = player.cast_mode() == CastMode::Autocast;
+
+        Self {
+            levels: player.levels,
+            boosts: Boosts::default(),
+            gear: Gear {
+                head: item_name(equipped.head),
+                cape: item_name(equipped.cape),
+                neck: item_name(equipped.neck),
+                ammunition: item_name(equipped.ammunition),
+                loaded_ammunition: item_name(equipped.loaded_ammunition),
+                weapon: weapon_name(&equipped.wielded).map(str::to_owned),
+                shield,
+                body: item_name(equipped.body),
+                legs: item_name(equipped.legs),
+                hands: item_name(equipped.hands),
+                feet: item_name(equipped.feet),
+                ring: item_name(equipped.ring),
+            },
+            prayers: player
+                .active_prayers
+                .iter()
+                .map(|prayer| prayer.get_name().to_owned())
+                .collect(),
+            spellbook: player.spellbook(),
+            spell: player.spell().map(|spell| spell.name.clone()),
+            casting: match player.combat_option().weapon_style {
+                _ if !autocasting => Casting::Manual,
+                WeaponStyle::DefensiveAutocast => Casting::DefensiveAutocast,
+                _ => Casting::Autocast,
+            },
+            style: (!autocasting).then(|| player.combat_option().name.clone()),
+            special_attack: player.special_attack_active(),
+            slayer_task: player.slayer_task,
+            extra: player.extra,
+        }
+    }
+}
+
+fn item_name<T: ContainsEquipment>(item: Option<&T>) -> Option<String> {
+    item.map(|item| item.inner().name.clone())
+}
+
+fn weapon_name<'a>(wielded: &Wielded<'a>) -> Option<&'a str> {
+    match *wielded {
+        Wielded::OneHanded { weapon, .. } => weapon.map(|weapon| weapon.inner.name.as_str()),
+        Wielded::TwoHanded { weapon } => weapon.map(|weapon| weapon.inner.name.as_str()),
+    }
+}
@@ -0,0 +1,576 @@
+use crate::{
+    bank::{Bank, Prices},
+    equipment::{ContainsEquipment, DamageBonus, Slot, Slots, StatBonuses, Stats, Wielded},
+    game_data::GameData,
+    generics::{to_f64, NamedData},
+    unit::{Enemy, Player},
+};
+
+/// Slots searched independently of the wielded weapon
+const ARMOUR_SLOTS: [Slot; 8] = [
+    Slot::Head,
+    Slot::Cape,
+    Slot::Neck,
+    Slot::Body,
+    Slot::Legs,
+    Slot::Hands,
+    Slot::Feet,
+    Slot::Ring,
+];
+
+/// What the gear search optimizes for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Objective {
+    #[default]
+    MaxDps,
+    /// The shortest time to kill the enemy, including the time to approach it
+    MinKillTime,
+}
+
+/// The best gear found, worn by a copy of the base player on its best combat style
+#[derive(Debug, Clone)]
+pub struct Optimized<'a> {
+    pub player: Player<'a>,
+    pub dps: f64,
+    pub kill_time: f64,
+    /// The GP spent on items that are not owned
+    pub cost: u64,
+    /// How many complete and partial loadouts were evaluated, for gauging the pruning
+    pub evaluations: usize,
+}
+
+/// Searches the candidate items for the gear that performs best against an enemy.
+///
+/// Every combination of weapon and ammunition is tried, with a shield only for one handed
+/// weapons. Each slot may also keep the base player's item, or stay empty if it has none, so that
+/// an item is only worn when it helps. Items without attributes that another item in the same
+/// slot matches or beats on every offensive stat are dropped, and branches whose best possible
+/// stats cannot beat the best loadout found so far are cut. Items with attributes are always
+/// searched, as their bonuses cannot be judged by stats alone.
+///
+/// The pruning assumes that raising any offensive stat of an item without attributes never lowers
+/// the score, which holds as long as accuracy rolls and max hits only grow with the bonuses. An
+/// item that breaks this, such as one whose stats enable a worse combat style, needs an attribute
+/// so that it is always searched.
+///
+/// The search can be restricted to owned items, with other items bought within a budget.
+#[derive(Debug, Clone)]
+pub struct Optimizer<'a> {
+    base: Player<'a>,
+    candidates: Vec<&'a Slots>,
+    objective: Objective,
+    owned: Option<Bank>,
+    budget: Option<Budget>,
+}
+
+#[derive(Debug, Clone)]
+struct Budget {
+    gp: u64,
+    prices: Prices,
+}
+
+/// A purchase and how much it improves the best loadout
+#[derive(Debug, Clone, PartialEq)]
+pub struct Upgrade {
+    pub name: String,
+    pub price: u64,
+    /// The increase in DPS of the best loadout, averaged over the enemies
+    pub dps_gain: f64,
+    pub dps_per_gp: f64,
+}
+
+impl<'a> Optimizer<'a> {
+    /// A search that keeps the base player's levels, prayers, spell and context. Slots without
+    /// candidates keep the base player's items.
+    pub fn new(base: Player<'a>) -> Self {
+        Self {
+            base,
+            candidates: Vec::new(),
+            objective: Objective::default(),
+            owned: None,
+            budget: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_candidates(mut self, items: impl IntoIterator<Item = &'a Slots>) -> Self {
+        self.candidates.extend(items);
+        self.candidates
+            .sort_by(|lhs, rhs| lhs.get_name().cmp(rhs.get_name()));
+        self.candidates
+            .dedup_by(|lhs, rhs| lhs.get_name() == rhs.get_name());
+        self
+    }
+
+    /// Adds every item in the data as a candidate
+    #[must_use]
+    pub fn with_all_items(self, data: &'a GameData) -> Self {
+        self.with_candidates(data.items().values())
+    }
+
+    #[must_use]
+    pub fn objective(mut self, objective: Objective) -> Self {
+        self.objective = objective;
+        self
+    }
+
+    /// Restricts the search to the owned items, along with any bought within the budget. Without
+    /// a bank every candidate counts as owned.
+    #[must_use]
+    pub fn owned(mut self, bank: Bank) -> Self {
+        self.owned = Some(bank);
+        self
+    }
+
+    /// Allows buying items that are not owned for up to `gp` in total. Items without a price
+    /// cannot be bought. Only has an effect alongside `owned`, as otherwise nothing is bought.
+    #[must_use]
+    pub fn budget(mut self, gp: u64, prices: Prices) -> Self {
+        self.budget = Some(Budget { gp, prices });
+        self
+    }
+
+    /// The best loadout of the candidates against the enemy, `None` if no combination can attack
+    /// it
+    pub fn optimize(&self, enemy: &Enemy) -> Option<Optimized<'a>> {
+        let mut search = Search {
+            enemy,
+            objective: self.objective,
+            budget: self.budget.as_ref().map(|budget| budget.gp),
+            best: None,
+            evaluations: 0,
+        };
+        let armour: Vec<Choice> = ARMOUR_SLOTS
+            .into_iter()
+            .filter_map(|slot| Choice::new(&self.base, slot, self.in_slot(slot), false, |_| false))
+            .collect();
+
+        let players = std::iter::once((self.base.clone(), 0)).chain(
+            self.in_slot(Slot::Weapon)
+                .into_iter()
+                .map(|(weapon, cost)| (self.base.clone().equip(weapon), cost)),
+        );
+        for (player, cost) in players {
+            let mut choices = armour.clone();
+            choices.extend(self.weapon_choices(&player));
+            // Choices that cannot be bounded by their stats go first, so that the rest of the
+            // search can be pruned
+            choices.sort_by_key(|choice| choice.bound.is_some());
+            search.branch(player, &choices, cost);
+        }
+
+        search.best.map(|best| Optimized {
+            player: best.player,
+            dps: best.dps,
+            kill_time: best.kill_time,
+            cost: best.cost,
+            evaluations: search.evaluations,
+        })
+    }
+
+    /// The candidates worth buying at the prices, ranked by the DPS they add per GP to the best
+    /// loadout of owned items against the enemies. Each item is judged on its own, and items that
+    /// add no DPS, have no price or cost more than the budget are left out. Without a bank every
+    /// candidate counts as owned, so there is nothing to buy.
+    pub fn rank_upgrades(&self, enemies: &[&Enemy], prices: &Prices) -> Vec<Upgrade> {
+        let Some(owned) = &self.owned else {
+            return Vec::new();
+        };
+        let mean_dps = |bank: Bank| {
+            let optimizer = Self {
+                owned: Some(bank),
+                budget: None,
+                objective: Objective::MaxDps,
+                ..self.clone()
+            };
+            let total: f64 = enemies
+                .iter()
+                .map(|enemy| optimizer.optimize(enemy).map_or(0.0, |best| best.dps))
+                .sum();
+            total / to_f64(enemies.len().max(1) as u64)
+        };
+        let current = mean_dps(owned.clone());
+
+        let mut upgrades: Vec<Upgrade> = self
+            .candidates
+            .iter()
+            .filter(|item| !owned.contains(item.get_name()))
+            .filter_map(|item| {
+                let name = item.get_name();
+                let price = prices.price(name).filter(|price| {
+                    *price > 0
+                        && self
+                            .budget
+                            .as_ref()
+                            .is_none_or(|budget| *price <= budget.gp)
+                })?;
+                let dps_gain = mean_dps(owned.clone().with_item(name)) - current;
+                (dps_gain > 0.0).then(|| Upgrade {
+                    name: name.to_owned(),
+                    price,
+                    dps_gain,
+                    dps_per_gp: dps_gain / to_f64(price),
+                })
+            })
+            .collect();
+        upgrades.sort_by(|lhs, rhs| rhs.dps_per_gp.total_cmp(&lhs.dps_per_gp));
+        upgrades
+    }
+
+    /// The GP the item costs to use, 0 if it is owned, or `None` if it cannot be bought
+    fn cost(&self, item: &Slots) -> Option<u64> {
+        let name = item.get_name();
+        if self.owned.as_ref().is_none_or(|bank| bank.contains(name)) {
+            return Some(0);
+        }
+        let budget = self.budget.as_ref()?;
+        budget
+            .prices
+            .price(name)
+            .filter(|price| *price <= budget.gp)
+    }
+
+    /// The usable items in the slot with their cost
+    fn in_slot(&self, slot: Slot) -> Vec<(&'a Slots, u64)> {
+        self.candidates
+            .iter()
+            .filter(|item| item.slot() == slot)
+            .filter_map(|item| Some((*item, self.cost(item)?)))
+            .collect()
+    }
+
+    /// The shield and ammunition choices that fit the player's weapon
+    fn weapon_choices(&self, player: &Player<'a>) -> Vec<Choice<'a>> {
+        let wielded = player.equipped().wielded;
+        let unnamed = |_: &Slots| false;
+        let mut choices = Vec::new();
+        if matches!(wielded, Wielded::OneHanded { .. }) {
+            let shields = self.in_slot(Slot::Shield);
+            choices.extend(Choice::new(player, Slot::Shield, shields, false, unnamed));
+        }
+        // The special attack can depend on the name of the ammunition fired
+        let special_ammunition = |item: &Slots| {
+            player.special_attack_active()
+                && wielded.special_attack().is_some_and(|special_attack| {
+                    special_attack.ammunition.contains_key(item.get_name())
+                })
+        };
+
+        let (accepted, other): (Vec<_>, Vec<_>) = self
+            .in_slot(Slot::Ammunition)
+            .into_iter()
+            .partition(|(item, _)| {
+                wielded.ammunition_requirement().is_some_and(|requirement| {
+                    matches!(item, Slots::Ammunition(ammunition) if requirement.accepts(ammunition))
+                })
+            });
+        match wielded.ammunition_requirement() {
+            Some(requirement) if requirement.embedded => {
+                choices.extend(Choice::new(
+                    player,
+                    Slot::Ammunition,
+                    accepted,
+                    true,
+                    special_ammunition,
+                ));
+                choices.extend(Choice::new(player, Slot::Ammunition, other, false, unnamed));
+            }
+            Some(_) => choices.extend(Choice::new(
+                player,
+                Slot::Ammunition,
+                accepted,
+                false,
+                special_ammunition,
+            )),
+            None => choices.extend(Choice::new(player, Slot::Ammunition, other, false, unnamed)),
+        }
+        choices
+    }
+}
+
+/// The items that may fill one slot, or be loaded into the weapon
+#[derive(Debug, Clone)]
+struct Choice<'a> {
+    /// The items with their cost, where `None` keeps what the player already has in the slot
+    items: Vec<(Option<&'a Slots>, u64)>,
+    loaded: bool,
+    /// A stand-in with the best offensive stats of all the items, if none of them have attributes
+    bound: Option<Slots>,
+}
+
+impl<'a> Choice<'a> {
+    /// The items along with keeping the player's own item for free, `None` if there are no items.
+    ///
+    /// Drops the items without attributes that another item without attributes matches or beats
+    /// on every offensive stat for no more GP. Items that `by_name` matches have bonuses tied to
+    /// their name and are treated like items with attributes.
+    fn new(
+        player: &Player,
+        slot: Slot,
+        items: Vec<(&'a Slots, u64)>,
+        loaded: bool,
+        by_name: impl Fn(&Slots) -> bool,
+    ) -> Option<Self> {
+        if items.is_empty() {
+            return None;
+        }
+        let worn = worn(player, slot, loaded);
+        // What an item leaves in the slot, `None` when the slot is left empty
+        let in_slot = |item: Option<&'a Slots>| item.or(worn.as_ref());
+        let stats_only =
+            |item: Option<&Slots>| item.is_none_or(|item| stats_only(item) && !by_name(item));
+        let stats =
+            |item: Option<&Slots>| item.map_or_else(Stats::default, |item| item.inner().stats);
+
+        let items: Vec<(Option<&Slots>, u64)> = std::iter::once((None, 0))
+            .chain(items.into_iter().map(|(item, cost)| (Some(item), cost)))
+            .collect();
+        let items: Vec<(Option<&Slots>, u64)> = items
+            .iter()
+            .enumerate()
+            .filter(|(index, (item, cost))| {
+                let item = in_slot(*item);
+                !stats_only(item)
+                    || !items
+                        .iter()
+                        .enumerate()
+                        .any(|(other_index, (other, other_cost))| {
+                            let other = in_slot(*other);
+                            let (stats, other_stats) = (stats(item), stats(other));
+                            let equal = dominates(&stats, &other_stats) && cost == other_cost;
+                            other_index != *index
+                                && stats_only(other)
+                                && other_cost <= cost
+                                && dominates(&other_stats, &stats)
+                                && (!equal || other_index < *index)
+                        })
+            })
+            .map(|(_, item)| *item)
+            .collect();
+
+        // Keeping the player's item is not a choice on its own
+        let template = items.iter().find_map(|(item, _)| *item)?;
+        let bound = items
+            .iter()
+            .all(|(item, _)| stats_only(in_slot(*item)))
+            .then(|| {
+                let mut all_stats = items.iter().map(|(item, _)| stats(in_slot(*item)));
+                let first = all_stats.next().unwrap_or_default();
+                bound_item(
+                    template,
+                    all_stats.fold(first, |best, stats| best_stats(best, &stats)),
+                )
+            });
+
+        Some(Self {
+            items,
+            loaded,
+            bound,
+        })
+    }
+
+    fn wear<'p>(&self, player: Player<'p>, item: Option<&'p Slots>) -> Option<Player<'p>> {
+        match item {
+            None => Some(player),
+            Some(item) if self.loaded => player.load_ammunition(item).ok(),
+            Some(item) => Some(player.equip(item)),
+        }
+    }
+}
+
+struct Scored<'a> {
+    player: Player<'a>,
+    score: f64,
+    dps: f64,
+    kill_time: f64,
+    cost: u64,
+}
+
+struct Search<'e, 'a> {
+    enemy: &'e Enemy,
+    objective: Objective,
+    budget: Option<u64>,
+    best: Option<Scored<'a>>,
+    evaluations: usize,
+}
+
+impl<'a> Search<'_, 'a> {
+    fn branch(&mut self, player: Player<'a>, choices: &[Choice<'a>], spent: u64) {
+        let Some((choice, rest)) = choices.split_first() else {
+            if let Some(scored) = self.evaluate(&player) {
+                if self
+                    .best
+                    .as_ref()
+                    .is_none_or(|best| scored.score > best.score)
+                {
+                    self.best = Some(Scored {
+                        cost: spent,
+                        ..scored
+                    });
+                }
+            }
+            return;
+        };
+        if self.cannot_improve(&player, choices) {
+            return;
+        }
+
+        for (item, cost) in &choice.items {
+            let spent = spent + cost;
+            if self.budget.is_some_and(|budget| spent > budget) {
+                continue;
+            }
+            if let Some(player) = choice.wear(player.clone(), *item) {
+                self.branch(player, rest, spent);
+            }
+        }
+    }
+
+    /// Whether wearing the best stats of every remaining choice is no better than the best
+    /// loadout found. Only sound when none of the remaining items have attributes.
+    fn cannot_improve(&mut self, player: &Player<'a>, choices: &[Choice<'a>]) -> bool {
+        let Some(best_score) = self.best.as_ref().map(|best| best.score) else {
+            return false;
+        };
+        // Bounding a single remaining loadout costs as much as evaluating it
+        let loadouts: usize = choices.iter().map(|choice| choice.items.len()).product();
+        if loadouts <= 1 || choices.iter().any(|choice| choice.bound.is_none()) {
+            return false;
+        }
+
+        let mut bound: Player<'_> = player.clone();
+        for choice in choices {
+            let Some(item) = &choice.bound else {
+                return false;
+            };
+            let Some(player) = choice.wear(bound, Some(item)) else {
+                return false;
+            };
+            bound = player;
+        }
+        self.evaluate(&bound)
+            .is_none_or(|scored| scored.score <= best_score)
+    }
+
+    /// The player on the combat style that scores best, of the styles `rank_combat_styles` ranks
+    fn evaluate<'p>(&mut self, player: &Player<'p>) -> Option<Scored<'p>> {
+        self.evaluations += 1;
+        let mut best: Option<Scored> = None;
+        for player in player.on_each_combat_style() {
+            let (Ok(dps), Ok(kill_time)) =
+                (player.try_dps(self.enemy), player.try_kill_time(self.enemy))
+            else {
+                continue;
+            };
+            let score = match self.objective {
+                Objective::MaxDps => dps,
+                Objective::MinKillTime => -kill_time,
+            };
+            if best.as_ref().is_none_or(|best| score > best.score) {
+                best = Some(Scored {
+                    player,
+                    score,
+                    dps,
+                    kill_time,
+                    cost: 0,
+                });
+            }
+        }
+        best
+    }
+}
+
+/// A copy of the item the player wears in the slot, or has loaded into its weapon
+fn worn(player: &Player, slot: Slot, loaded: bool) -> Option<Slots> {
+    let equipped = player.equipped();
+    match (slot, equipped.wielded) {
+        (Slot::Head, _) => equipped.head.cloned().map(Slots::Head),
+        (Slot::Cape, _) => equipped.cape.cloned().map(Slots::Cape),
+        (Slot::Neck, _) => equipped.neck.cloned().map(Slots::Neck),
+        (Slot::Ammunition, _) if loaded => {
+            equipped.loaded_ammunition.cloned().map(Slots::Ammunition)
+        }
+        (Slot::Ammunition, _) => equipped.ammunition.cloned().map(Slots::Ammunition),
+        (Slot::Weapon, Wielded::OneHanded { weapon, .. }) => {
+            weapon.cloned().map(Slots::WeaponOneHanded)
+        }
+        (Slot::Weapon, Wielded::TwoHanded { weapon }) => {
+            weapon.cloned().map(Slots::WeaponTwoHanded)
+        }
+        (Slot::Shield, Wielded::OneHanded { shield, .. }) => shield.cloned().map(Slots::Shield),
+        (Slot::Shield, Wielded::TwoHanded { .. }) => None,
+        (Slot::Body, _) => equipped.body.cloned().map(Slots::Body),
+        (Slot::Legs, _) => equipped.legs.cloned().map(Slots::Legs),
+        (Slot::Hands, _) => equipped.hands.cloned().map(Slots::Hands),
+        (Slot::Feet, _) => equipped.feet.cloned().map(Slots::Feet),
+        (Slot::Ring, _) => equipped.ring.cloned().map(Slots::Ring),
+    }
+}
+
+/// Whether the item's bonuses all come from its stats
+fn stats_only(item: &Slots) -> bool {
+    item.inner().attributes.is_empty()
+        && !matches!(item, Slots::Ammunition(ammunition) if ammunition.bolt_proc.is_some())
+}
+
+/// Whether every offensive stat of `lhs` is at least that of `rhs`
+fn dominates(lhs: &Stats, rhs: &Stats) -> bool {
+    let (lhs_attack, rhs_attack) = (&lhs.attack, &rhs.attack);
+    let (lhs_damage, rhs_damage) = (&lhs.damage, &rhs.damage);
+    lhs_attack.stab >= rhs_attack.stab
+        && lhs_attack.slash >= rhs_attack.slash
+        && lhs_attack.crush >= rhs_attack.crush
+        && lhs_attack.ranged >= rhs_attack.ranged
+        && lhs_attack.magic >= rhs_attack.magic
+        && lhs_damage.strength >= rhs_damage.strength
+        && lhs_damage.ranged >= rhs_damage.ranged
+        && lhs_damage.magic >= rhs_damage.magic
+}
+
+/// The highest of each offensive stat
+fn best_stats(lhs: Stats, rhs: &Stats) -> Stats {
+    let (lhs_attack, rhs_attack) = (&lhs.attack, &rhs.attack);
+    let (lhs_damage, rhs_damage) = (&lhs.damage, &rhs.damage);
+    Stats {
+        attack: StatBonuses {
+            stab: lhs_attack.stab.max(rhs_attack.stab),
+            slash: lhs_attack.slash.max(rhs_attack.slash),
+            crush: lhs_attack.crush.max(rhs_attack.crush),
+            ranged: lhs_attack.ranged.max(rhs_attack.ranged),
+            magic: lhs_attack.magic.max(rhs_attack.magic),
+        },
+        damage: DamageBonus {
+            strength: lhs_damage.strength.max(rhs_damage.strength),
+            ranged: lhs_damage.ranged.max(rhs_damage.ranged),
+            magic: if rhs_damage.magic > lhs_damage.magic {
+                rhs_damage.magic
+            } else {
+                lhs_damage.magic
+            },
+        },
+        ..lhs
+    }
+}
+
+/// A stand-in for the items of a choice with their best stats, named so that it cannot be
+/// mistaken for the item it is copied from
+fn bound_item(item: &Slots, stats: Stats) -> Slots {
+    let mut bound = item.clone();
+    let inner = match &mut bound {
+        Slots::Head(v) => &mut v.inner,
+        Slots::Cape(v) => &mut v.inner,
+        Slots::Neck(v) => &mut v.inner,
+        Slots::Ammunition(v) => &mut v.inner,
+        Slots::WeaponOneHanded(v) => &mut v.inner,
+        Slots::WeaponTwoHanded(v) => &mut v.inner,
+        Slots::Shield(v) => &mut v.inner,
+        Slots::Body(v) => &mut v.inner,
+        Slots::Legs(v) => &mut v.inner,
+        Slots::Hands(v) => &mut v.inner,
+        Slots::Feet(v) => &mut v.inner,
+        Slots::Ring(v) => &mut v.inner,
+    };
+    inner.name = format!("Best {:?} stats", item.slot());
+    inner.stats = stats;
+    bound
+}
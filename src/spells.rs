@@ -1,5 +1,5 @@
-use crate::generics::{NamedData, Scalar};
-use serde::Deserialize;
+use crate::generics::{NamedData, Scalar, Ticks};
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Spell {
@@ -7,22 +7,54 @@ pub struct Spell {
     pub max_hit: Scalar,
     pub spellbook: Spellbook,
     pub attributes: Vec<Attribute>,
+    #[serde(default)]
+    pub element: Option<Element>,
+    /// Casting speed, if it differs from the usual 5 ticks
+    #[serde(default)]
+    pub attack_speed: Option<Ticks>,
 }
 
-#[derive(Debug, Deserialize, Clone, Copy)]
+pub const DEFAULT_CAST_SPEED: i32 = 5;
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Element {
+    Air,
+    Water,
+    Earth,
+    Fire,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
 pub enum Attribute {
     Bolt,
+    Burst,
     Barrage,
 }
 
-#[derive(Debug, Deserialize, Clone, Copy)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
 pub enum Spellbook {
+    #[default]
     Standard,
     Ancient,
     Lunar,
     Arceuus,
 }
 
+/// How the selected spell is cast. Manual casts work with any weapon, autocasts require a
+/// weapon that can autocast the spell and one of its `Autocast` or `DefensiveAutocast` styles.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CastMode {
+    #[default]
+    Manual,
+    Autocast,
+}
+
+impl Spell {
+    pub fn has_attribute(&self, attribute: &Attribute) -> bool {
+        self.attributes.contains(attribute)
+    }
+}
+
 impl NamedData for Spell {
     fn get_name(&self) -> &str {
         &self.name
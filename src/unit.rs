@@ -1,15 +1,28 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::cmp::min;
 
 use crate::{
+    damage::{
+        double_roll_hit_chance, hit_chance, AttackResult, HitRange, HitRangeModifier, Hitsplat,
+        MissDamage, Proc,
+    },
     equipment::{
-        combat_styles::{CombatOption, StyleType},
-        weapon_callbacks::Callbacks,
+        bolt_effects::BoltProc,
+        combat_styles::{CombatOption, CombatOptionModifier, StyleType, WeaponStyle, WeaponType},
+        weapon_callbacks::{Attribute, BonusGroup, Callbacks},
         Ammunition, Body, Cape, ContainsEquipment, Equipment, Feet, Hands, Head, Legs, Neck,
         PoweredStaff, Ring, Slots, Stats, Wielded,
     },
-    generics::{NamedData, Scalar, Ticks, Tiles, SECONDS_PER_TICK},
+    error::{self, Error},
+    explain::{Breakdown, StepKind, Trace},
+    generics::{
+        to_f64, Fraction, NamedData, Percentage, Scalar, Ticks, Tiles, MAX_ATTACK_RANGE,
+        SECONDS_PER_TICK,
+    },
     prayers::Prayer,
-    spells::Spell,
+    spells::{
+        Attribute as SpellAttribute, CastMode, Element, Spell, Spellbook, DEFAULT_CAST_SPEED,
+    },
 };
 
 #[derive(Debug, Deserialize, Clone)]
@@ -19,6 +32,34 @@ pub struct Enemy {
     pub stats: Stats,
     pub attributes: Vec<EnemyAttribute>,
     pub size: Tiles,
+    #[serde(default)]
+    pub magic_defence: MagicDefence,
+    #[serde(default)]
+    pub elemental_weakness: Option<ElementalWeakness>,
+    /// Slayer tasks the enemy counts towards
+    #[serde(default)]
+    pub slayer_categories: Vec<SlayerCategory>,
+}
+
+/// Spells of the given element gain `severity` percent accuracy and `severity` percent of the
+/// spell's base max hit as extra damage
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub struct ElementalWeakness {
+    pub element: Element,
+    pub severity: Percentage,
+}
+
+/// The level an NPC defends against magic attacks with
+#[derive(Debug, Deserialize, Clone, Copy, Default)]
+pub enum MagicDefence {
+    #[default]
+    Magic,
+    Defence,
+    /// Each level is scaled by its fraction and the results are summed
+    Blend {
+        magic: Fraction,
+        defence: Fraction,
+    },
 }
 
 #[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
@@ -30,6 +71,45 @@ pub enum EnemyAttribute {
     Vampyre,
     Leafy,
     Undead,
+    Fiery,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum SlayerCategory {
+    AberrantSpectres,
+    AbyssalDemons,
+    AdamantDragons,
+    Ankou,
+    Basilisks,
+    BlackDemons,
+    BlackDragons,
+    Bloodveld,
+    BlueDragons,
+    CaveKraken,
+    Dagannoth,
+    DarkBeasts,
+    Drakes,
+    DustDevils,
+    FireGiants,
+    Gargoyles,
+    GreaterDemons,
+    Hellhounds,
+    Hydras,
+    IronDragons,
+    Kalphites,
+    Kurasks,
+    MithrilDragons,
+    Nechryael,
+    RedDragons,
+    RuneDragons,
+    SkeletalWyverns,
+    Skeletons,
+    SmokeDevils,
+    SteelDragons,
+    Trolls,
+    Vampyres,
+    Wyrms,
+    Zombies,
 }
 
 impl NamedData for Enemy {
@@ -46,11 +126,12 @@ impl Enemy {
             StyleType::Crush => self.stats.defence.crush,
             StyleType::Ranged => self.stats.defence.ranged,
             StyleType::Magic => self.stats.defence.magic,
-            StyleType::None => unimplemented!(),
+            // Non-attacking styles never need to be defended against
+            StyleType::None => return 0.into(),
         };
 
         let effective_defence_level = if let StyleType::Magic = style_type {
-            self.levels.magic
+            self.magic_defence_level()
         } else {
             self.levels.defence
         } + 9.into();
@@ -58,26 +139,52 @@ impl Enemy {
         effective_defence_level * (style_defence + 64.into())
     }
 
+    pub fn magic_defence_level(&self) -> Scalar {
+        match self.magic_defence {
+            MagicDefence::Magic => self.levels.magic,
+            MagicDefence::Defence => self.levels.defence,
+            MagicDefence::Blend { magic, defence } => {
+                self.levels.magic * magic + self.levels.defence * defence
+            }
+        }
+    }
+
     pub fn has_attribute(&self, attribute: &EnemyAttribute) -> bool {
         self.attributes.contains(attribute)
     }
+
+    pub fn is_slayer_category(&self, category: &SlayerCategory) -> bool {
+        self.slayer_categories.contains(category)
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Extra {
-    pub on_slayer_task: bool,
     pub mining_level: Scalar,
     pub in_wilderness: bool,
+    /// Whether the wielded wilderness weapon has revenant ether charges, without which it loses
+    /// its bonus against NPCs in the wilderness
+    pub wilderness_weapon_charged: bool,
     pub charge_active: bool,
+    pub kandarin_hard_diary: bool,
+    /// Whether the target is another player rather than an NPC
+    pub pvp: bool,
+    /// Distance from the player to the closest tile of the target, 1 being adjacent and 0 standing
+    /// underneath it
+    pub distance: Tiles,
 }
 
 impl Default for Extra {
     fn default() -> Self {
         Self {
-            on_slayer_task: true,
             mining_level: 99.into(),
-            in_wilderness: true,
+            in_wilderness: false,
+            wilderness_weapon_charged: true,
             charge_active: false,
+            kandarin_hard_diary: false,
+            pvp: false,
+            distance: 1.into(),
         }
     }
 }
@@ -88,7 +195,12 @@ pub struct Player<'a> {
     equipped: Equipped<'a>,
     pub active_prayers: Vec<&'a Prayer>,
     combat_option: CombatOption,
-    pub spell: Option<&'a Spell>,
+    spell: Option<&'a Spell>,
+    cast_mode: CastMode,
+    spellbook: Spellbook,
+    special_attack: bool,
+    /// The player's current slayer assignment, if any
+    pub slayer_task: Option<SlayerCategory>,
     pub extra: Extra,
 }
 
@@ -96,13 +208,41 @@ impl<'a> Player<'a> {
     #[must_use]
     pub fn equip_full(mut self, equipped: Equipped<'a>) -> Self {
         self.equipped = equipped;
-        self.update_combat_option();
+        self.update_wielded();
         self
     }
 
+    fn update_wielded(&mut self) {
+        self.update_combat_option();
+        if !self.equipped.loaded_ammunition_accepted() {
+            self.equipped.loaded_ammunition = None;
+        }
+    }
+
+    /// Keeps the new weapon's style closest to the current one, or its first style if none are
+    /// alike
     fn update_combat_option(&mut self) {
-        // Should not panic as there should always be at least 3 combat options
-        self.combat_option = self.equipped.wielded.combat_boost().remove(0);
+        let can_autocast = self
+            .spell
+            .is_some_and(|spell| self.equipped.wielded.can_autocast(spell));
+        if self.cast_mode == CastMode::Autocast && !can_autocast {
+            self.spell = None;
+            self.cast_mode = CastMode::Manual;
+        }
+
+        let mut combat_options = self.equipped.wielded.combat_boost();
+        let mut closest = (0, 0);
+        for (index, combat_option) in combat_options.iter().enumerate() {
+            if combat_option.is_autocast() && self.cast_mode != CastMode::Autocast {
+                continue;
+            }
+            let equivalence = combat_option.equivalence(&self.combat_option);
+            if equivalence > closest.1 {
+                closest = (index, equivalence);
+            }
+        }
+        // Should not panic as there should always be at least 2 combat options
+        self.combat_option = combat_options.remove(closest.0);
     }
 
     #[must_use]
@@ -117,12 +257,125 @@ impl<'a> Player<'a> {
         self
     }
 
+    /// Switches spellbook, dropping the selected spell if it is not part of the new spellbook
     #[must_use]
-    pub fn select_spell(mut self, spell: &'a Spell) -> Self {
+    pub fn set_spellbook(mut self, spellbook: Spellbook) -> Self {
+        self.spellbook = spellbook;
+        if self.spell.is_some_and(|spell| spell.spellbook != spellbook) {
+            self.spell = None;
+            self.cast_mode = CastMode::Manual;
+        }
+        self
+    }
+
+    /// Selects a spell to cast manually, which works with any weapon
+    ///
+    /// # Errors
+    /// Returns an error if the spell is not part of the active spellbook
+    pub fn select_spell(mut self, spell: &'a Spell) -> error::Result<Self> {
+        self.check_spellbook(spell)?;
+        self.spell = Some(spell);
+        self.cast_mode = CastMode::Manual;
+        Ok(self)
+    }
+
+    /// Selects a spell to autocast and switches to the weapon's autocast style
+    ///
+    /// # Errors
+    /// Returns an error if the spell is not part of the active spellbook or if the wielded weapon
+    /// cannot autocast it
+    pub fn autocast_spell(mut self, spell: &'a Spell, defensive: bool) -> error::Result<Self> {
+        self.check_spellbook(spell)?;
+        if !self.equipped.wielded.can_autocast(spell) {
+            return Err(Error::CannotAutocast {
+                spell: spell.name.clone(),
+            });
+        }
+
+        let weapon_style = if defensive {
+            WeaponStyle::DefensiveAutocast
+        } else {
+            WeaponStyle::Autocast
+        };
+        self.combat_option = self
+            .equipped
+            .wielded
+            .combat_boost()
+            .into_iter()
+            .find(|combat_option| combat_option.weapon_style == weapon_style)
+            .ok_or(Error::MissingCombatStyle {
+                style_type: StyleType::Magic,
+                weapon_style,
+            })?;
         self.spell = Some(spell);
+        self.cast_mode = CastMode::Autocast;
+        Ok(self)
+    }
+
+    fn check_spellbook(&self, spell: &Spell) -> error::Result<()> {
+        if spell.spellbook == self.spellbook {
+            Ok(())
+        } else {
+            Err(Error::WrongSpellbook {
+                spell: spell.name.clone(),
+                spellbook: self.spellbook,
+            })
+        }
+    }
+
+    #[must_use]
+    pub fn assign_slayer_task(mut self, slayer_task: Option<SlayerCategory>) -> Self {
+        self.slayer_task = slayer_task;
+        self
+    }
+
+    /// Whether the enemy counts towards the player's current slayer task
+    pub fn on_slayer_task(&self, enemy: &Enemy) -> bool {
+        self.slayer_task
+            .is_some_and(|slayer_task| enemy.is_slayer_category(&slayer_task))
+    }
+
+    /// Uses the wielded weapon's special attack for the next attack, if it has one
+    #[must_use]
+    pub fn use_special_attack(mut self, active: bool) -> Self {
+        self.special_attack = active;
         self
     }
 
+    pub fn special_attack_active(&self) -> bool {
+        self.special_attack && self.equipped.wielded.special_attack().is_some()
+    }
+
+    pub fn spell(&self) -> Option<&Spell> {
+        self.spell
+    }
+
+    pub fn cast_mode(&self) -> CastMode {
+        self.cast_mode
+    }
+
+    pub fn spellbook(&self) -> Spellbook {
+        self.spellbook
+    }
+
+    /// Whether the next attack is a spell, either cast manually or autocast from the current
+    /// combat style
+    pub fn is_casting(&self) -> bool {
+        match self.cast_mode {
+            CastMode::Manual => self.spell.is_some(),
+            CastMode::Autocast => self.spell.is_some() && self.combat_option.is_autocast(),
+        }
+    }
+
+    /// The style type of the next attack, taking spellcasting into account
+    pub fn style_type(&self) -> StyleType {
+        if self.is_casting() {
+            StyleType::Magic
+        } else {
+            self.combat_option.style_type
+        }
+    }
+
     #[must_use]
     pub fn equip(mut self, slot: &'a Slots) -> Self {
         match slot {
@@ -141,11 +394,11 @@ impl<'a> Player<'a> {
                             Wielded::equip_one_handed(Some(weapon_one_handed), None);
                     }
                 };
-                self.update_combat_option();
+                self.update_wielded();
             }
             Slots::WeaponTwoHanded(weapon_two_handed) => {
                 self.equipped.wielded = Wielded::equip_two_handed(Some(weapon_two_handed));
-                self.update_combat_option();
+                self.update_wielded();
             }
             Slots::Body(body) => self.equipped.body = Some(body),
             Slots::Shield(new_shield) => {
@@ -157,7 +410,7 @@ impl<'a> Player<'a> {
                         self.equipped.wielded = Wielded::equip_one_handed(None, Some(new_shield));
                     }
                 };
-                self.update_combat_option();
+                self.update_wielded();
             }
             Slots::Legs(legs) => self.equipped.legs = Some(legs),
             Slots::Hands(hands) => self.equipped.hands = Some(hands),
@@ -168,7 +421,33 @@ impl<'a> Player<'a> {
         self
     }
 
-    pub fn equipped(&self) -> &Equipped {
+    /// Loads ammunition into a weapon that holds its own, like darts into a blowpipe
+    ///
+    /// # Errors
+    /// Returns an error if the item is not ammunition or the wielded weapon cannot be loaded with it
+    pub fn load_ammunition(mut self, slot: &'a Slots) -> error::Result<Self> {
+        let Slots::Ammunition(ammunition) = slot else {
+            return Err(Error::NotAmmunition {
+                item: slot.inner().name.clone(),
+            });
+        };
+
+        if !self
+            .equipped
+            .wielded
+            .ammunition_requirement()
+            .is_some_and(|requirement| requirement.embedded && requirement.accepts(ammunition))
+        {
+            return Err(Error::CannotLoadAmmunition {
+                item: ammunition.inner.name.clone(),
+            });
+        }
+
+        self.equipped.loaded_ammunition = Some(ammunition);
+        Ok(self)
+    }
+
+    pub fn equipped(&self) -> &Equipped<'_> {
         &self.equipped
     }
 
@@ -177,15 +456,85 @@ impl<'a> Player<'a> {
     }
 
     /// # Errors
-    /// Returns an error if the index is invalid for the currently wielded weapon
-    pub fn change_combat_style(&mut self, index: usize) -> Result<(), &str> {
+    /// Returns an error if the index is invalid for the currently wielded weapon, or if it is an
+    /// autocast style and no spell the weapon can autocast is selected
+    pub fn change_combat_style(&mut self, index: usize) -> error::Result<()> {
         let mut combat_options = self.equipped.wielded.combat_boost();
-        if index < combat_options.len() {
-            self.combat_option = combat_options.remove(index);
-            Ok(())
-        } else {
-            Err("Invalid index")
+        if index >= combat_options.len() {
+            return Err(Error::InvalidCombatStyleIndex { index });
+        }
+
+        let combat_option = combat_options.remove(index);
+        if combat_option.is_autocast() {
+            if !self
+                .spell
+                .is_some_and(|spell| self.equipped.wielded.can_autocast(spell))
+            {
+                return Err(Error::NoAutocastSpell);
+            }
+            self.cast_mode = CastMode::Autocast;
         }
+        self.combat_option = combat_option;
+        Ok(())
+    }
+
+    /// Selects the wielded weapon's combat style with the given name, ignoring case. Styles that
+    /// share a name, like a staff's two autocast styles, select the first.
+    ///
+    /// # Errors
+    /// Returns an error if the weapon has no style with the name, or if it is an autocast style and
+    /// no spell the weapon can autocast is selected
+    pub fn select_combat_style(&mut self, name: &str) -> error::Result<()> {
+        let combat_options = self.equipped.wielded.combat_boost();
+        let index = combat_options
+            .iter()
+            .position(|combat_option| combat_option.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| Error::UnknownCombatStyle {
+                name: name.to_owned(),
+                options: combat_options
+                    .into_iter()
+                    .map(|combat_option| combat_option.name)
+                    .collect(),
+            })?;
+        self.change_combat_style(index)
+    }
+
+    /// Selects the wielded weapon's combat style that attacks with the style type and weapon style
+    ///
+    /// # Errors
+    /// Returns an error if the weapon has no such style, or if it is an autocast style and no spell
+    /// the weapon can autocast is selected
+    pub fn select_combat_style_by_type(
+        &mut self,
+        style_type: StyleType,
+        weapon_style: WeaponStyle,
+    ) -> error::Result<()> {
+        let index = self
+            .equipped
+            .wielded
+            .combat_boost()
+            .iter()
+            .position(|combat_option| {
+                combat_option.style_type == style_type && combat_option.weapon_style == weapon_style
+            })
+            .ok_or(Error::MissingCombatStyle {
+                style_type,
+                weapon_style,
+            })?;
+        self.change_combat_style(index)
+    }
+
+    /// The wielded weapon's combat styles in order, each with its invisible boost
+    pub fn combat_options(&self) -> Vec<(CombatOption, CombatOptionModifier)> {
+        self.equipped
+            .wielded
+            .combat_boost()
+            .into_iter()
+            .map(|combat_option| {
+                let boost = combat_option.invisible_boost().unwrap_or_default();
+                (combat_option, boost)
+            })
+            .collect()
     }
 
     pub fn prayer_stats(&self) -> crate::prayers::Stats {
@@ -194,194 +543,725 @@ impl<'a> Player<'a> {
             .fold(crate::prayers::Stats::default(), |acc, p| acc + p.stats)
     }
 
-    pub fn max_melee_accuracy_roll(&self, enemy: &Enemy) -> Scalar {
-        let mut effective_attack_level = self.levels.attack * self.prayer_stats().melee_accuracy;
-        effective_attack_level += self
+    /// The player's defence roll against an attack of the given style type. Magic attacks are
+    /// defended with 70% of the magic level and 30% of the defence level.
+    pub fn max_defence_roll(&self, style_type: &StyleType) -> Scalar {
+        let stats = self.equipped.total_stats();
+        let style_defence = match style_type {
+            StyleType::Stab => stats.defence.stab,
+            StyleType::Slash => stats.defence.slash,
+            StyleType::Crush => stats.defence.crush,
+            StyleType::Ranged => stats.defence.ranged,
+            StyleType::Magic => stats.defence.magic,
+            // Non-attacking styles never need to be defended against
+            StyleType::None => return 0.into(),
+        };
+
+        let prayer_stats = self.prayer_stats();
+        let defence_level = self.levels.defence * prayer_stats.defence;
+        let mut effective_defence_level = if let StyleType::Magic = style_type {
+            self.levels.magic * prayer_stats.magic_defence * Fraction::new(7, 10)
+                + defence_level * Fraction::new(3, 10)
+        } else {
+            defence_level
+        };
+        // Invalid combat options give no boost
+        effective_defence_level += self
             .combat_option
             .invisible_boost()
-            .expect("Valid combat style")
-            .attack;
-        effective_attack_level += 8.into();
+            .unwrap_or_default()
+            .defence;
+        effective_defence_level += 8.into();
+
+        effective_defence_level * (style_defence + 64.into())
+    }
+
+    pub fn max_magic_defence_roll(&self) -> Scalar {
+        self.max_defence_roll(&StyleType::Magic)
+    }
+
+    pub fn max_melee_accuracy_roll(&self, enemy: &Enemy) -> Scalar {
+        self.max_melee_accuracy_roll_traced(enemy, &mut Trace::disabled())
+            .unwrap_or_default()
+    }
+
+    fn max_melee_accuracy_roll_traced(
+        &self,
+        enemy: &Enemy,
+        trace: &mut Trace,
+    ) -> error::Result<Scalar> {
+        let level = trace.record(StepKind::Level, self.levels.attack, self.levels.attack);
+        let mut effective_attack_level = trace.record(
+            StepKind::Prayer,
+            level,
+            level * self.prayer_stats().melee_accuracy,
+        );
+        effective_attack_level = trace.record(
+            StepKind::InvisibleBoost,
+            effective_attack_level,
+            effective_attack_level + self.combat_option.invisible_boost()?.attack,
+        );
+        effective_attack_level = trace.record(
+            StepKind::EffectiveLevel,
+            effective_attack_level,
+            effective_attack_level + 8.into(),
+        );
 
         let style_bonus = match self.combat_option.style_type {
             StyleType::Stab => self.equipped.total_stats().attack.stab,
             StyleType::Slash => self.equipped.total_stats().attack.slash,
             StyleType::Crush => self.equipped.total_stats().attack.crush,
-            _ => unreachable!(),
+            style_type => return Err(Error::UnexpectedStyleType { style_type }),
         };
 
-        let mut attack_roll = effective_attack_level * (style_bonus + 64.into());
+        let attack_roll = trace.record(
+            StepKind::EquipmentBonus,
+            effective_attack_level,
+            effective_attack_level * (style_bonus + 64.into()),
+        );
 
-        attack_roll = self
+        Ok(self
             .equipped
-            .accuracy_roll_callback(attack_roll, self, enemy);
-
-        attack_roll
+            .accuracy_roll_callback_traced(attack_roll, self, enemy, trace))
     }
 
     pub fn max_melee_hit(&self, enemy: &Enemy) -> Scalar {
-        let mut effective_strength_level = self.levels.strength * self.prayer_stats().melee_damage;
-        effective_strength_level += self
-            .combat_option
-            .invisible_boost()
-            .expect("Valid combat style")
-            .strength;
-        effective_strength_level += 8.into();
-
-        let mut max_hit = (effective_strength_level
-            * (self.equipped.total_stats().damage.strength + 64.into())
-            + 320.into())
-            / 640.into();
-
-        max_hit = self.equipped.max_hit_callback(max_hit, self, enemy);
+        self.max_melee_hit_traced(enemy, &mut Trace::disabled())
+            .unwrap_or_default()
+    }
 
-        max_hit
+    fn max_melee_hit_traced(&self, enemy: &Enemy, trace: &mut Trace) -> error::Result<Scalar> {
+        let level = trace.record(StepKind::Level, self.levels.strength, self.levels.strength);
+        let mut effective_strength_level = trace.record(
+            StepKind::Prayer,
+            level,
+            level * self.prayer_stats().melee_damage,
+        );
+        effective_strength_level = trace.record(
+            StepKind::InvisibleBoost,
+            effective_strength_level,
+            effective_strength_level + self.combat_option.invisible_boost()?.strength,
+        );
+        effective_strength_level = trace.record(
+            StepKind::EffectiveLevel,
+            effective_strength_level,
+            effective_strength_level + 8.into(),
+        );
+
+        let max_hit = trace.record(
+            StepKind::EquipmentBonus,
+            effective_strength_level,
+            (effective_strength_level * (self.equipped.total_stats().damage.strength + 64.into())
+                + 320.into())
+                / 640.into(),
+        );
+
+        Ok(self
+            .equipped
+            .max_hit_callback_traced(max_hit, self, enemy, trace))
     }
 
     pub fn max_ranged_accuracy_roll(&self, enemy: &Enemy) -> Scalar {
-        let mut effective_ranged_level = self.levels.ranged * self.prayer_stats().ranged_accuracy;
-        effective_ranged_level += self
-            .combat_option
-            .invisible_boost()
-            .expect("Valid combat style")
-            .ranged;
-        effective_ranged_level += 8.into();
+        self.max_ranged_accuracy_roll_traced(enemy, &mut Trace::disabled())
+            .unwrap_or_default()
+    }
+
+    fn max_ranged_accuracy_roll_traced(
+        &self,
+        enemy: &Enemy,
+        trace: &mut Trace,
+    ) -> error::Result<Scalar> {
+        let level = trace.record(StepKind::Level, self.levels.ranged, self.levels.ranged);
+        let mut effective_ranged_level = trace.record(
+            StepKind::Prayer,
+            level,
+            level * self.prayer_stats().ranged_accuracy,
+        );
+        effective_ranged_level = trace.record(
+            StepKind::InvisibleBoost,
+            effective_ranged_level,
+            effective_ranged_level + self.combat_option.invisible_boost()?.ranged,
+        );
+        effective_ranged_level = trace.record(
+            StepKind::EffectiveLevel,
+            effective_ranged_level,
+            effective_ranged_level + 8.into(),
+        );
 
         let style_bonus = match self.combat_option.style_type {
             StyleType::Ranged => self.equipped.total_stats().attack.ranged,
-            _ => unreachable!(),
+            style_type => return Err(Error::UnexpectedStyleType { style_type }),
         };
 
-        let mut attack_roll = effective_ranged_level * (style_bonus + 64.into());
+        let mut attack_roll = trace.record(
+            StepKind::EquipmentBonus,
+            effective_ranged_level,
+            effective_ranged_level * (style_bonus + 64.into()),
+        );
 
         attack_roll = self
             .equipped
-            .accuracy_roll_callback(attack_roll, self, enemy);
+            .accuracy_roll_callback_traced(attack_roll, self, enemy, trace);
+
+        if let Some(modifier) = self.chinchompa_accuracy_modifier() {
+            attack_roll = trace.record(
+                StepKind::ChinchompaFuse,
+                attack_roll,
+                attack_roll * modifier,
+            );
+        }
 
-        attack_roll
+        Ok(attack_roll)
     }
 
-    pub fn max_ranged_hit(&self, enemy: &Enemy) -> Scalar {
-        let mut effective_ranged_level = self.levels.ranged * self.prayer_stats().ranged_damage;
-        effective_ranged_level += self
-            .combat_option
-            .invisible_boost()
-            .expect("Valid combat style")
-            .ranged;
-        effective_ranged_level += 8.into();
+    /// Chinchompas are most accurate at close range with a short fuse, medium range with a medium
+    /// fuse and long range with a long fuse, losing a quarter of their accuracy per band away
+    pub fn chinchompa_accuracy_modifier(&self) -> Option<Fraction> {
+        if !matches!(
+            self.equipped.wielded.weapon_stats().weapon_type,
+            WeaponType::Chinchompa
+        ) {
+            return None;
+        }
 
-        let mut max_hit = (effective_ranged_level
-            * (self.equipped.total_stats().damage.ranged + 64.into())
-            + 320.into())
-            / 640.into();
+        let band = match *Scalar::from(self.extra.distance) {
+            ..=3 => 0,
+            4..=6 => 1,
+            _ => 2,
+        };
+        let best_band = match self.combat_option.weapon_style {
+            WeaponStyle::ShortFuse => 0,
+            WeaponStyle::MediumFuse => 1,
+            WeaponStyle::LongFuse => 2,
+            _ => return None,
+        };
 
-        max_hit = self.equipped.max_hit_callback(max_hit, self, enemy);
+        Some(Fraction::new(4 - i32::abs(band - best_band), 4))
+    }
 
-        max_hit
+    pub fn max_ranged_hit(&self, enemy: &Enemy) -> Scalar {
+        self.max_ranged_hit_traced(enemy, &mut Trace::disabled())
+            .unwrap_or_default()
+    }
+
+    fn max_ranged_hit_traced(&self, enemy: &Enemy, trace: &mut Trace) -> error::Result<Scalar> {
+        let level = trace.record(StepKind::Level, self.levels.ranged, self.levels.ranged);
+        let mut effective_ranged_level = trace.record(
+            StepKind::Prayer,
+            level,
+            level * self.prayer_stats().ranged_damage,
+        );
+        effective_ranged_level = trace.record(
+            StepKind::InvisibleBoost,
+            effective_ranged_level,
+            effective_ranged_level + self.combat_option.invisible_boost()?.ranged,
+        );
+        effective_ranged_level = trace.record(
+            StepKind::EffectiveLevel,
+            effective_ranged_level,
+            effective_ranged_level + 8.into(),
+        );
+
+        let max_hit = trace.record(
+            StepKind::EquipmentBonus,
+            effective_ranged_level,
+            (effective_ranged_level * (self.equipped.total_stats().damage.ranged + 64.into())
+                + 320.into())
+                / 640.into(),
+        );
+
+        Ok(self
+            .equipped
+            .max_hit_callback_traced(max_hit, self, enemy, trace))
     }
 
     pub fn max_magic_accuracy_roll(&self, enemy: &Enemy) -> Scalar {
-        let mut effective_magic_level = self.levels.magic * self.prayer_stats().magic_accuracy;
-        effective_magic_level += self
-            .combat_option
-            .invisible_boost()
-            .expect("Valid combat style")
-            .magic;
-        effective_magic_level += 8.into();
-        if self.spell.is_some() {
-            effective_magic_level += 1.into();
+        self.max_magic_accuracy_roll_traced(enemy, &mut Trace::disabled())
+            .unwrap_or_default()
+    }
+
+    fn max_magic_accuracy_roll_traced(
+        &self,
+        enemy: &Enemy,
+        trace: &mut Trace,
+    ) -> error::Result<Scalar> {
+        let level = trace.record(StepKind::Level, self.levels.magic, self.levels.magic);
+        let mut effective_magic_level = trace.record(
+            StepKind::Prayer,
+            level,
+            level * self.prayer_stats().magic_accuracy,
+        );
+        if self.is_casting() {
+            // Spells never receive a style bonus, autocast styles have none and manual casts
+            // ignore the current style
+            effective_magic_level = trace.record(
+                StepKind::EffectiveLevel,
+                effective_magic_level,
+                effective_magic_level + 9.into(),
+            );
+        } else {
+            effective_magic_level = trace.record(
+                StepKind::InvisibleBoost,
+                effective_magic_level,
+                effective_magic_level + self.combat_option.invisible_boost()?.magic,
+            );
+            effective_magic_level = trace.record(
+                StepKind::EffectiveLevel,
+                effective_magic_level,
+                effective_magic_level + 8.into(),
+            );
         }
 
         let magic_bonus = self.equipped.total_stats().attack.magic;
 
-        let mut attack_roll = effective_magic_level * (magic_bonus + 64.into());
+        let mut attack_roll = trace.record(
+            StepKind::EquipmentBonus,
+            effective_magic_level,
+            effective_magic_level * (magic_bonus + 64.into()),
+        );
 
         attack_roll = self
             .equipped
-            .accuracy_roll_callback(attack_roll, self, enemy);
+            .accuracy_roll_callback_traced(attack_roll, self, enemy, trace);
+
+        if let Some(severity) = self.elemental_weakness_severity(enemy) {
+            attack_roll = trace.record(
+                StepKind::ElementalWeakness,
+                attack_roll,
+                attack_roll * severity,
+            );
+        }
 
-        attack_roll
+        Ok(attack_roll)
     }
 
-    pub fn max_magic_hit(&self, _enemy: &Enemy) -> Scalar {
-        let mut max_hit = if let Some(max_hit) = self.equipped.powered_staff_max_hit(self) {
-            max_hit
-        } else if let Some(spell) = &self.spell {
+    /// The severity of the enemy's elemental weakness if the spell being cast matches it
+    pub fn elemental_weakness_severity(&self, enemy: &Enemy) -> Option<Percentage> {
+        let spell = self.spell.filter(|_| self.is_casting())?;
+        let weakness = enemy.elemental_weakness?;
+        (spell.element == Some(weakness.element)).then_some(weakness.severity)
+    }
+
+    pub fn max_magic_hit(&self, enemy: &Enemy) -> Scalar {
+        self.max_magic_hit_traced(enemy, &mut Trace::disabled())
+            .unwrap_or_default()
+    }
+
+    fn max_magic_hit_traced(&self, enemy: &Enemy, trace: &mut Trace) -> error::Result<Scalar> {
+        let base_max_hit = if let Some(spell) = self.spell.filter(|_| self.is_casting()) {
             spell.max_hit
+        } else if let Some(max_hit) = self.equipped.powered_staff_max_hit(self) {
+            max_hit
         } else {
-            unimplemented!()
+            return Err(Error::NoMagicMaxHit);
         };
+        let mut max_hit = trace.record(StepKind::BaseMaxHit, base_max_hit, base_max_hit);
 
         let magic_damage_bonus = self.equipped.total_stats().damage.magic;
 
-        max_hit = max_hit * magic_damage_bonus;
+        max_hit = trace.record(
+            StepKind::EquipmentBonus,
+            max_hit,
+            max_hit * magic_damage_bonus,
+        );
+
+        if let (Some(severity), Some(spell)) = (self.elemental_weakness_severity(enemy), self.spell)
+        {
+            max_hit = trace.record(
+                StepKind::ElementalWeakness,
+                max_hit,
+                max_hit + spell.max_hit * Fraction::from(severity),
+            );
+        }
 
-        max_hit
+        Ok(max_hit)
     }
 
+    /// The accuracy roll against the enemy, zero if the player cannot attack
     pub fn max_accuracy_roll(&self, enemy: &Enemy) -> Scalar {
-        if let Some(_spell) = &self.spell {
-            self.max_magic_accuracy_roll(enemy)
-        } else {
-            match self.combat_option.style_type {
-                StyleType::Stab | StyleType::Slash | StyleType::Crush => {
-                    self.max_melee_accuracy_roll(enemy)
-                }
-                StyleType::Ranged => self.max_ranged_accuracy_roll(enemy),
-                StyleType::Magic => self.max_magic_accuracy_roll(enemy),
-                StyleType::None => unimplemented!(),
+        self.try_max_accuracy_roll(enemy).unwrap_or_default()
+    }
+
+    /// # Errors
+    /// Returns an error if the current combat style does not attack or cannot deal damage
+    pub fn try_max_accuracy_roll(&self, enemy: &Enemy) -> error::Result<Scalar> {
+        self.max_accuracy_roll_traced(enemy, &mut Trace::disabled())
+    }
+
+    fn max_accuracy_roll_traced(&self, enemy: &Enemy, trace: &mut Trace) -> error::Result<Scalar> {
+        match self.style_type() {
+            StyleType::Stab | StyleType::Slash | StyleType::Crush => {
+                self.max_melee_accuracy_roll_traced(enemy, trace)
             }
+            StyleType::Ranged => self.max_ranged_accuracy_roll_traced(enemy, trace),
+            StyleType::Magic => self.max_magic_accuracy_roll_traced(enemy, trace),
+            StyleType::None => Err(Error::NonAttackingStyle),
         }
     }
 
+    /// The max hit against the enemy, zero if the player cannot attack
     pub fn max_hit(&self, enemy: &Enemy) -> Scalar {
-        if let Some(_spell) = &self.spell {
-            self.max_magic_hit(enemy)
-        } else {
-            match self.combat_option.style_type {
-                StyleType::Stab | StyleType::Slash | StyleType::Crush => self.max_melee_hit(enemy),
-                StyleType::Ranged => self.max_ranged_hit(enemy),
-                StyleType::Magic => self.max_magic_hit(enemy),
-                StyleType::None => unimplemented!(),
+        self.try_max_hit(enemy).unwrap_or_default()
+    }
+
+    /// # Errors
+    /// Returns an error if the current combat style does not attack or cannot deal damage
+    pub fn try_max_hit(&self, enemy: &Enemy) -> error::Result<Scalar> {
+        self.max_hit_traced(enemy, &mut Trace::disabled())
+    }
+
+    fn max_hit_traced(&self, enemy: &Enemy, trace: &mut Trace) -> error::Result<Scalar> {
+        match self.style_type() {
+            StyleType::Stab | StyleType::Slash | StyleType::Crush => {
+                self.max_melee_hit_traced(enemy, trace)
             }
+            StyleType::Ranged => self.max_ranged_hit_traced(enemy, trace),
+            StyleType::Magic => self.max_magic_hit_traced(enemy, trace),
+            StyleType::None => Err(Error::NonAttackingStyle),
         }
     }
 
-    pub fn dps(&self, enemy: &Enemy) -> f64 {
-        let style_type = if self.spell.is_some() {
-            &StyleType::Magic
+    /// Every step of the accuracy roll and max hit calculations against the enemy, alongside the
+    /// resulting hit chance and DPS
+    ///
+    /// # Errors
+    /// Returns an error if the current combat style does not attack or cannot deal damage
+    pub fn explain(&self, enemy: &Enemy) -> error::Result<Breakdown> {
+        let mut accuracy_trace = Trace::enabled();
+        let accuracy_roll = self.max_accuracy_roll_traced(enemy, &mut accuracy_trace)?;
+        let mut max_hit_trace = Trace::enabled();
+        let max_hit = self.max_hit_traced(enemy, &mut max_hit_trace)?;
+        let enemy_defence_roll = enemy.max_defence_roll(&self.style_type());
+
+        Ok(Breakdown {
+            accuracy_roll,
+            accuracy_roll_steps: accuracy_trace.into_steps(),
+            max_hit,
+            max_hit_steps: max_hit_trace.into_steps(),
+            enemy_defence_roll,
+            hit_chance: self.attack_hit_chance(accuracy_roll, enemy_defence_roll),
+            dps: self.try_dps(enemy)?,
+        })
+    }
+
+    /// The chance for an attack with the accuracy roll to hit, rolling twice for Osmumten's fang
+    fn attack_hit_chance(&self, accuracy_roll: Scalar, enemy_defence_roll: Scalar) -> f64 {
+        if self.style_type().is_melee()
+            && self
+                .equipped
+                .wielded
+                .weapon_has_attribute(&Attribute::OsmumtensFang)
+        {
+            double_roll_hit_chance(accuracy_roll, enemy_defence_roll)
+        } else {
+            hit_chance(accuracy_roll, enemy_defence_roll)
+        }
+    }
+
+    /// Every combat style of the wielded weapon that can attack the enemy, ranked by DPS with the
+    /// highest first. Autocast styles are included when the selected spell can be autocast with the
+    /// weapon, and the other styles are evaluated as weapon attacks without a spell.
+    pub fn rank_combat_styles(&self, enemy: &Enemy) -> Vec<StyleEvaluation> {
+        let mut evaluations: Vec<StyleEvaluation> = self
+            .on_each_combat_style()
+            .iter()
+            .filter_map(|player| player.evaluate_combat_style(enemy).ok())
+            .collect();
+        evaluations.sort_by(|lhs, rhs| rhs.dps.total_cmp(&lhs.dps));
+        evaluations
+    }
+
+    /// A copy of the player on each combat style that `rank_combat_styles` evaluates, casting the
+    /// selected spell on autocast styles and attacking with the weapon on the others
+    pub(crate) fn on_each_combat_style(&self) -> Vec<Self> {
+        let can_autocast = self
+            .spell
+            .is_some_and(|spell| self.equipped.wielded.can_autocast(spell));
+        self.equipped
+            .wielded
+            .combat_boost()
+            .into_iter()
+            .filter(|combat_option| can_autocast || !combat_option.is_autocast())
+            .map(|combat_option| {
+                let mut player = self.clone();
+                if combat_option.is_autocast() {
+                    player.cast_mode = CastMode::Autocast;
+                } else {
+                    player.spell = None;
+                    player.cast_mode = CastMode::Manual;
+                }
+                player.combat_option = combat_option;
+                player
+            })
+            .collect()
+    }
+
+    fn evaluate_combat_style(&self, enemy: &Enemy) -> error::Result<StyleEvaluation> {
+        let accuracy_roll = self.try_max_accuracy_roll(enemy)?;
+        let enemy_defence_roll = enemy.max_defence_roll(&self.style_type());
+
+        Ok(StyleEvaluation {
+            combat_option: self.combat_option.clone(),
+            accuracy_roll,
+            hit_chance: self.attack_hit_chance(accuracy_roll, enemy_defence_roll),
+            max_hit: self.try_max_hit(enemy)?,
+            attack_speed: self.try_attack_speed(enemy)?,
+            dps: self.try_dps(enemy)?,
+        })
+    }
+
+    /// Adjustments to the hit range of the next attack declared by the weapon or its special
+    /// attack, which may depend on the ammunition fired
+    pub fn hit_range_modifier(&self) -> Option<HitRangeModifier> {
+        let wielded = &self.equipped.wielded;
+        match wielded.special_attack().filter(|_| self.special_attack) {
+            Some(special_attack) => Some(
+                self.equipped
+                    .fired_ammunition()
+                    .and_then(|ammunition| special_attack.ammunition.get(&ammunition.inner.name))
+                    .copied()
+                    .unwrap_or(special_attack.hit_range),
+            ),
+            None => wielded.hit_range_modifier(),
+        }
+    }
+
+    /// The damage range of a successful hit with the next attack. Unlike `max_hit`, this includes
+    /// minimum hits, damage caps and special attack damage declared in the data.
+    pub fn hit_range(&self, enemy: &Enemy) -> HitRange {
+        self.try_hit_range(enemy).unwrap_or_default()
+    }
+
+    /// # Errors
+    /// Returns an error if the current combat style does not attack or cannot deal damage
+    pub fn try_hit_range(&self, enemy: &Enemy) -> error::Result<HitRange> {
+        let max_hit = self.try_max_hit(enemy)?;
+        Ok(self
+            .hit_range_modifier()
+            .map_or(HitRange::up_to(max_hit), |modifier| modifier.apply(max_hit)))
+    }
+
+    /// The hitsplats dealt by the next attack, none if the player cannot attack
+    pub fn attack_result(&self, enemy: &Enemy) -> AttackResult {
+        self.try_attack_result(enemy).unwrap_or_default()
+    }
+
+    /// The hitsplats dealt by the next attack. Multi-hit weapons deal several hitsplats, each
+    /// rolling accuracy and damage independently.
+    ///
+    /// # Errors
+    /// Returns an error if the current combat style does not attack or cannot deal damage
+    pub fn try_attack_result(&self, enemy: &Enemy) -> error::Result<AttackResult> {
+        let style_type = self.style_type();
+        let max_enemy_defence_roll = enemy.max_defence_roll(&style_type);
+        let max_accuracy_roll = self.try_max_accuracy_roll(enemy)?;
+        let range = self.try_hit_range(enemy)?;
+        let accuracy = self.attack_hit_chance(max_accuracy_roll, max_enemy_defence_roll);
+        let hitsplat = Hitsplat {
+            accuracy,
+            range,
+            on_miss: self
+                .hit_range_modifier()
+                .map_or(MissDamage::Zero, |modifier| modifier.on_miss),
+        };
+        let wielded = &self.equipped.wielded;
+
+        let hitsplats = if style_type.is_melee()
+            && wielded.weapon_has_attribute(&Attribute::ScytheOfVitur)
+        {
+            // Each additional hit deals half the damage of the previous one, one extra hit per
+            // tile of enemy size up to a total of three
+            let hits = min(*Scalar::from(enemy.size), 3).max(1);
+            (0..hits)
+                .map(|hit| Hitsplat::up_to(accuracy, range.max / Scalar::new(1 << hit)))
+                .collect()
+        } else if style_type.is_melee() && wielded.weapon_has_attribute(&Attribute::OsmumtensFang) {
+            let clamp = range.max * Fraction::new(3, 20);
+            vec![Hitsplat::new(
+                accuracy,
+                HitRange::new(clamp, range.max - clamp),
+            )]
+        } else if style_type.is_ranged() && wielded.weapon_has_attribute(&Attribute::DarkBow) {
+            vec![hitsplat; 2]
         } else {
-            &self.combat_option.style_type
+            vec![hitsplat]
         };
-        let max_enemy_defence_roll: i32 = enemy.max_defence_roll(style_type).into();
-        let max_accuracy_roll: i32 = self.max_accuracy_roll(enemy).into();
-        let max_hit: i32 = self.max_hit(enemy).into();
-        let attack_speed: i32 = if let Some(_spell) = &self.spell {
-            self.equipped
-                .attack_speed_callback(5.into(), self, enemy)
-                .into()
+
+        let proc_effect = self.bolt_proc().and_then(|bolt_proc| {
+            bolt_proc
+                .hitsplat(self, enemy, accuracy, range.max)
+                .map(|hitsplat| Proc {
+                    chance: bolt_proc.chance(self),
+                    hitsplats: vec![hitsplat],
+                })
+        });
+
+        Ok(AttackResult {
+            hitsplats,
+            proc_effect,
+        })
+    }
+
+    /// The special effect of the enchanted bolts being fired, if any
+    pub fn bolt_proc(&self) -> Option<&BoltProc> {
+        self.equipped
+            .fired_ammunition()
+            .and_then(|ammunition| ammunition.bolt_proc.as_ref())
+            .filter(|_| self.style_type().is_ranged())
+    }
+
+    /// Ticks between attacks, the weapon's listed speed if the combat style is invalid
+    pub fn attack_speed(&self, enemy: &Enemy) -> Ticks {
+        self.try_attack_speed(enemy)
+            .unwrap_or_else(|_| self.equipped.wielded.weapon_stats().attack_speed)
+    }
+
+    /// Ticks between attacks. Spells use their casting speed regardless of weapon, while other
+    /// attacks use the weapon's speed adjusted by the combat style, such as Rapid. Weapon effects
+    /// like the Harmonised nightmare staff's faster standard spells are applied last.
+    ///
+    /// # Errors
+    /// Returns an error if the combat style is invalid
+    pub fn try_attack_speed(&self, enemy: &Enemy) -> error::Result<Ticks> {
+        let attack_speed = if let Some(spell) = self.spell.filter(|_| self.is_casting()) {
+            spell.attack_speed.unwrap_or(DEFAULT_CAST_SPEED.into())
         } else {
             self.equipped
                 .wielded
-                .attack_speed(&self.combat_option)
-                .into()
+                .attack_speed(&self.combat_option, self.extra.pvp)?
         };
 
-        let max_accuracy_roll: f64 = max_accuracy_roll.into();
-        let max_enemy_defence_roll: f64 = max_enemy_defence_roll.into();
-        let max_hit: f64 = max_hit.into();
-        let attack_speed: f64 = attack_speed.into();
+        Ok(self
+            .equipped
+            .attack_speed_callback(attack_speed, self, enemy))
+    }
+
+    /// Damage per second against the enemy, zero if the player cannot attack
+    pub fn dps(&self, enemy: &Enemy) -> f64 {
+        self.try_dps(enemy).unwrap_or(0.0)
+    }
+
+    /// # Errors
+    /// Returns an error if the current combat style does not attack or cannot deal damage
+    pub fn try_dps(&self, enemy: &Enemy) -> error::Result<f64> {
+        let attack_speed: f64 = i32::from(self.try_attack_speed(enemy)?).into();
+
+        Ok((self.try_attack_result(enemy)?.expected_damage() / attack_speed) / SECONDS_PER_TICK)
+    }
 
-        let hit_rate = if max_enemy_defence_roll > max_accuracy_roll {
-            0.5 * max_accuracy_roll / (max_enemy_defence_roll + 1.0)
+    /// The maximum distance the next attack can reach, 10 tiles for spells and otherwise the
+    /// weapon's range extended by the combat style, up to 10 tiles
+    pub fn attack_range(&self) -> Tiles {
+        if self.is_casting() {
+            return MAX_ATTACK_RANGE.into();
+        }
+
+        let mut attack_range = self.equipped.wielded.weapon_stats().range;
+        // Invalid combat options give no boost
+        attack_range += self
+            .combat_option
+            .invisible_boost()
+            .unwrap_or_default()
+            .attack_range;
+
+        min(attack_range, MAX_ATTACK_RANGE.into())
+    }
+
+    /// Whether the target can be attacked without moving
+    pub fn can_reach(&self) -> bool {
+        self.extra.distance > 0.into() && self.extra.distance <= self.attack_range()
+    }
+
+    /// Ticks spent running towards the target, two tiles per tick, before the first attack. A
+    /// player standing underneath the target first has to step out from below it.
+    pub fn approach_ticks(&self, enemy: &Enemy) -> Ticks {
+        let distance = *Scalar::from(self.extra.distance);
+        let attack_range = *Scalar::from(self.attack_range());
+
+        let tiles = if distance == 0 {
+            (*Scalar::from(enemy.size) + 1) / 2
+        } else {
+            (distance - attack_range).max(0)
+        };
+
+        ((tiles + 1) / 2).into()
+    }
+
+    /// Expected seconds to kill the target from full hitpoints, including the approach. Infinite
+    /// if the player cannot attack.
+    pub fn kill_time(&self, enemy: &Enemy) -> f64 {
+        self.try_kill_time(enemy).unwrap_or(f64::INFINITY)
+    }
+
+    /// # Errors
+    /// Returns an error if the current combat style does not attack or cannot deal damage
+    pub fn try_kill_time(&self, enemy: &Enemy) -> error::Result<f64> {
+        let hitpoints: f64 = (*enemy.levels.hitpoints).into();
+        let approach_ticks: f64 = i32::from(self.approach_ticks(enemy)).into();
+
+        Ok(approach_ticks * SECONDS_PER_TICK + hitpoints / self.try_dps(enemy)?)
+    }
+
+    /// Radius around the primary target that the next attack also damages, for burst and barrage
+    /// spells and chinchompas
+    pub fn area_radius(&self) -> Option<Tiles> {
+        let is_area_attack = if self.is_casting() {
+            self.spell.is_some_and(|spell| {
+                spell.has_attribute(&SpellAttribute::Burst)
+                    || spell.has_attribute(&SpellAttribute::Barrage)
+            })
         } else {
-            1f64 - (0.5 * (max_enemy_defence_roll + 2.0) / (max_accuracy_roll + 1.0))
+            self.style_type().is_ranged()
+                && matches!(
+                    self.equipped.wielded.weapon_stats().weapon_type,
+                    WeaponType::Chinchompa
+                )
         };
 
-        ((hit_rate * max_hit / 2.0) / attack_speed) / SECONDS_PER_TICK
+        is_area_attack.then(|| 1.into())
+    }
+
+    /// The number of targets the next attack can hit, a 3x3 area for a radius of 1
+    pub fn max_targets(&self) -> usize {
+        self.area_radius().map_or(1, |radius| {
+            let width = usize::try_from(2 * *Scalar::from(radius) + 1).unwrap_or(1);
+            width * width
+        })
+    }
+
+    /// Total damage per second across all targets packed around the first target in a multi-combat
+    /// area. Accuracy and damage are calculated separately against each target and single target
+    /// attacks only hit the first target.
+    ///
+    /// Positions are not modelled: the first `max_targets` enemies are assumed to be in range,
+    /// whatever their size, and the rest are ignored, so order the targets by priority.
+    pub fn area_dps(&self, targets: &[&Enemy]) -> f64 {
+        targets
+            .iter()
+            .take(self.max_targets())
+            .map(|enemy| self.dps(enemy))
+            .sum()
+    }
+
+    /// Total damage per second against `count` copies of the same enemy in a multi-combat area,
+    /// assuming as many as the attack can reach are in range like `area_dps`
+    pub fn area_dps_against(&self, enemy: &Enemy, count: usize) -> f64 {
+        let targets = count.min(self.max_targets());
+        self.dps(enemy) * to_f64(targets as u64)
     }
 }
 
+/// How a combat style performs against an enemy
+#[derive(Debug, Clone)]
+pub struct StyleEvaluation {
+    pub combat_option: CombatOption,
+    pub accuracy_roll: Scalar,
+    pub hit_chance: f64,
+    pub max_hit: Scalar,
+    pub attack_speed: Ticks,
+    pub dps: f64,
+}
+
 impl Default for Player<'_> {
     fn default() -> Self {
         Self {
@@ -390,12 +1270,16 @@ impl Default for Player<'_> {
             active_prayers: Vec::default(),
             combat_option: Equipped::default().wielded.combat_boost().remove(0),
             spell: None,
+            cast_mode: CastMode::default(),
+            spellbook: Spellbook::default(),
+            special_attack: false,
+            slayer_task: None,
             extra: Extra::default(),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Levels {
     pub hitpoints: Scalar,
     pub attack: Scalar,
@@ -426,6 +1310,8 @@ pub struct Equipped<'a> {
     pub cape: Option<&'a Cape>,
     pub neck: Option<&'a Neck>,
     pub ammunition: Option<&'a Ammunition>,
+    /// Ammunition loaded into a weapon that holds its own
+    pub loaded_ammunition: Option<&'a Ammunition>,
     pub wielded: Wielded<'a>,
     pub body: Option<&'a Body>,
     pub legs: Option<&'a Legs>,
@@ -461,47 +1347,144 @@ impl<'a> Iterator for EquippedIter<'a> {
 }
 
 impl Equipped<'_> {
-    pub fn iter(&self) -> EquippedIter {
+    pub fn iter(&self) -> EquippedIter<'_> {
         EquippedIter {
             inner: self,
             index: 0,
         }
     }
 
+    /// The ammunition fired by the wielded weapon, either from the ammunition slot or loaded into
+    /// the weapon
+    pub fn fired_ammunition(&self) -> Option<&Ammunition> {
+        let requirement = self.wielded.ammunition_requirement()?;
+        if requirement.embedded {
+            self.loaded_ammunition
+        } else {
+            self.ammunition
+        }
+        .filter(|ammunition| requirement.accepts(ammunition))
+    }
+
+    /// Whether the wielded weapon fires the ammunition in the ammunition slot
+    pub fn fires_ammunition(&self) -> bool {
+        self.wielded
+            .ammunition_requirement()
+            .zip(self.ammunition)
+            .is_some_and(|(requirement, ammunition)| {
+                !requirement.embedded && requirement.accepts(ammunition)
+            })
+    }
+
+    fn uses_loaded_ammunition(&self) -> bool {
+        self.wielded
+            .ammunition_requirement()
+            .is_some_and(|requirement| requirement.embedded)
+    }
+
+    /// Whether the equipped ammunition can be used with the wielded weapon. Weapons that do not
+    /// fire from the ammunition slot accept anything in it.
+    pub fn ammunition_compatible(&self) -> bool {
+        self.wielded.ammunition_requirement().is_none()
+            || self.uses_loaded_ammunition()
+            || self.fires_ammunition()
+    }
+
+    fn loaded_ammunition_accepted(&self) -> bool {
+        self.loaded_ammunition.is_none_or(|ammunition| {
+            self.wielded
+                .ammunition_requirement()
+                .is_some_and(|requirement| requirement.embedded && requirement.accepts(ammunition))
+        })
+    }
+
+    /// Total equipment stats. The ranged bonuses of ammunition only count when the wielded weapon
+    /// fires it, and only the ranged strength of ammunition loaded into the weapon counts.
     pub fn total_stats(&self) -> Stats {
         let armour_stats: Stats = self.iter().map(|equipment| equipment.stats).sum();
-        armour_stats + self.wielded.stats()
+        let mut stats = armour_stats + self.wielded.stats();
+
+        if !self.fires_ammunition() {
+            let ammunition_stats = self.ammunition.unwrap_or_default().inner.stats;
+            stats.attack.ranged -= ammunition_stats.attack.ranged;
+            stats.damage.ranged -= ammunition_stats.damage.ranged;
+        }
+        if let Some(loaded_ammunition) = self
+            .fired_ammunition()
+            .filter(|_| self.uses_loaded_ammunition())
+        {
+            stats.damage.ranged += loaded_ammunition.inner.stats.damage.ranged;
+        }
+
+        stats
     }
 
-    pub fn accuracy_roll_callback(
+    /// Attributes of every equipped item, the wielded weapon's last
+    pub fn attributes(&self) -> Vec<Attribute> {
+        self.iter()
+            .flat_map(|equipment| equipment.attributes.iter())
+            .chain(self.wielded.attributes())
+            .copied()
+            .collect()
+    }
+
+    /// Attributes whose bonuses are used in an attack against the enemy. Of the bonuses in a
+    /// non-stacking group, only the highest priority one that applies is kept, wherever it is
+    /// equipped.
+    pub fn active_attributes(&self, player: &Player, enemy: &Enemy) -> Vec<Attribute> {
+        let attributes = self.attributes();
+        let group_winner = |group: BonusGroup| {
+            attributes
+                .iter()
+                .filter_map(|attribute| {
+                    attribute
+                        .bonus_group()
+                        .filter(|(other_group, _)| *other_group == group)
+                        .map(|(_, priority)| (priority, *attribute))
+                })
+                .filter(|(_, attribute)| attribute.bonus_applies(player, enemy))
+                .min_by_key(|(priority, _)| *priority)
+                .map(|(_, attribute)| attribute)
+        };
+
+        attributes
+            .iter()
+            .copied()
+            .filter(|attribute| match attribute.bonus_group() {
+                Some((group, _)) => group_winner(group) == Some(*attribute),
+                None => true,
+            })
+            .collect()
+    }
+
+    pub fn accuracy_roll_callback(&self, value: Scalar, player: &Player, enemy: &Enemy) -> Scalar {
+        self.accuracy_roll_callback_traced(value, player, enemy, &mut Trace::disabled())
+    }
+
+    pub(crate) fn accuracy_roll_callback_traced(
         &self,
-        mut value: Scalar,
+        value: Scalar,
         player: &Player,
         enemy: &Enemy,
+        trace: &mut Trace,
     ) -> Scalar {
-        value = self.iter().fold(value, |value, equipent| {
-            equipent
-                .attributes
-                .accuracy_roll_callback(value, player, enemy)
-        });
-        value = self
-            .wielded
-            .attributes()
-            .accuracy_roll_callback(value, player, enemy);
-
-        value
+        self.active_attributes(player, enemy)
+            .accuracy_roll_callback(value, player, enemy, trace)
     }
 
-    pub fn max_hit_callback(&self, mut value: Scalar, player: &Player, enemy: &Enemy) -> Scalar {
-        value = self.iter().fold(value, |value, equipent| {
-            equipent.attributes.max_hit_callback(value, player, enemy)
-        });
-        value = self
-            .wielded
-            .attributes()
-            .max_hit_callback(value, player, enemy);
+    pub fn max_hit_callback(&self, value: Scalar, player: &Player, enemy: &Enemy) -> Scalar {
+        self.max_hit_callback_traced(value, player, enemy, &mut Trace::disabled())
+    }
 
-        value
+    pub(crate) fn max_hit_callback_traced(
+        &self,
+        value: Scalar,
+        player: &Player,
+        enemy: &Enemy,
+        trace: &mut Trace,
+    ) -> Scalar {
+        self.active_attributes(player, enemy)
+            .max_hit_callback(value, player, enemy, trace)
     }
 
     pub fn attack_speed_callback(&self, value: Ticks, player: &Player, enemy: &Enemy) -> Ticks {
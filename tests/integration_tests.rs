@@ -1,23 +1,24 @@
-use std::collections::HashMap;
+use std::cmp::min;
 
-use lazy_static::lazy_static;
 use osrs_dps_calc::{
-    equipment::{combat_styles::StyleType, Slots},
-    generics::read_file,
-    prayers::Prayer,
-    spells::Spell,
-    unit::{Enemy, Player},
+    bank::{Bank, Prices},
+    damage::{double_roll_hit_chance, hit_chance, HitRangeModifier, MissDamage},
+    equipment::{
+        combat_styles::{StyleType, WeaponStyle},
+        Attribute, ContainsEquipment, Slot, Slots,
+    },
+    error::Error,
+    explain::StepKind,
+    game_data::{DataErrorKind, DataFile, DataFiles, GameData},
+    generics::Fraction,
+    loadout::{Casting, Loadout, LoadoutError},
+    optimizer::{Objective, Optimizer},
+    spells::{Element, Spellbook},
+    unit::{Enemy, MagicDefence, Player, SlayerCategory},
 };
 
 type TResult<T> = Result<T, Box<dyn std::error::Error>>;
 
-lazy_static! {
-    static ref ITEMS: HashMap<String, Slots> = read_file("./data/equipment.json").unwrap();
-    static ref PRAYERS: HashMap<String, Prayer> = read_file("./data/prayers.json").unwrap();
-    static ref SPELLS: HashMap<String, Spell> = read_file("./data/spells.json").unwrap();
-    static ref ENEMIES: HashMap<String, Enemy> = read_file("./data/enemies.json").unwrap();
-}
-
 struct PlayerConstructor<'a> {
     player: Player<'a>,
 }
@@ -30,23 +31,35 @@ impl<'a> PlayerConstructor<'a> {
     }
 
     fn equip(mut self, slot: &str) -> TResult<Self> {
+        self.player = self.player.equip(GameData::bundled().item(slot)?);
+        Ok(self)
+    }
+
+    fn load_ammunition(mut self, ammunition: &str) -> TResult<Self> {
         self.player = self
             .player
-            .equip(ITEMS.get(slot).ok_or("Could not find item")?);
+            .load_ammunition(GameData::bundled().item(ammunition)?)?;
         Ok(self)
     }
 
     fn activate_prayer(mut self, prayer: &str) -> TResult<Self> {
         self.player = self
             .player
-            .activate_prayer(PRAYERS.get(prayer).ok_or("Could not find prayer")?);
+            .activate_prayer(GameData::bundled().prayer(prayer)?);
         Ok(self)
     }
 
     fn select_spell(mut self, spell: &str) -> TResult<Self> {
         self.player = self
             .player
-            .select_spell(SPELLS.get(spell).ok_or("Could not find spell")?);
+            .select_spell(GameData::bundled().spell(spell)?)?;
+        Ok(self)
+    }
+
+    fn autocast_spell(mut self, spell: &str) -> TResult<Self> {
+        self.player = self
+            .player
+            .autocast_spell(GameData::bundled().spell(spell)?, false)?;
         Ok(self)
     }
 
@@ -56,7 +69,7 @@ impl<'a> PlayerConstructor<'a> {
 }
 
 fn create_enemy(enemy: &str) -> TResult<&Enemy> {
-    Ok(ENEMIES.get(enemy).ok_or("Could not find enemy")?)
+    Ok(GameData::bundled().enemy(enemy)?)
 }
 
 fn assert_float_eq(lhs: f64, rhs: f64) {
@@ -74,7 +87,7 @@ fn test_standard_melee_accuracy() -> TResult<()> {
         .activate_prayer("Piety")?
         .build();
     let enemy = create_enemy("Fire giant (level 86)")?;
-    player.change_combat_style(1)?;
+    player.select_combat_style("Lash")?;
     assert_eq!(player.max_accuracy_roll(enemy), 21590.into());
     Ok(())
 }
@@ -87,7 +100,7 @@ fn test_standard_melee_max_hit() -> TResult<()> {
         .activate_prayer("Piety")?
         .build();
     let enemy = create_enemy("Fire giant (level 86)")?;
-    player.change_combat_style(1)?;
+    player.select_combat_style("Lash")?;
     assert_eq!(player.max_hit(enemy), 31.into());
     Ok(())
 }
@@ -106,7 +119,7 @@ fn test_standard_melee_dps_vs_enemy() -> TResult<()> {
         .equip("Dragon defender")?
         .activate_prayer("Piety")?
         .build();
-    player.change_combat_style(1)?;
+    player.select_combat_style("Lash")?;
     let enemy = create_enemy("Fire giant (level 86)")?;
     assert_float_eq(player.dps(enemy), 5.716_511_895_388_511_5);
     Ok(())
@@ -119,12 +132,36 @@ fn test_dragon_hunter_crossbow_accuracy() -> TResult<()> {
         .equip("Dragon bolts")?
         .activate_prayer("Rigour")?
         .build();
-    player.change_combat_style(1)?;
+    player.select_combat_style("Rapid")?;
     let enemy = create_enemy("Mithril dragon")?;
     assert_eq!(player.max_accuracy_roll(enemy), 26044.into());
     Ok(())
 }
 
+#[test]
+fn test_dragon_hunter_crossbow_accuracy_only_against_dragons() -> TResult<()> {
+    let mut player = PlayerConstructor::new()
+        .equip("Dragon hunter crossbow")?
+        .equip("Dragon bolts")?
+        .build();
+    player.select_combat_style("Rapid")?;
+    let callback = |enemy: &Enemy| -> TResult<Option<(i32, i32)>> {
+        Ok(player
+            .explain(enemy)?
+            .accuracy_roll_steps
+            .iter()
+            .find(|step| step.kind == StepKind::Callback(Attribute::DragonHunterCrossbow))
+            .map(|step| (i32::from(step.before), i32::from(step.after))))
+    };
+
+    let (before, after) = callback(create_enemy("Mithril dragon")?)?.ok_or("Missing DHCB step")?;
+    assert_eq!(after, before * 13 / 10);
+    let (before, after) =
+        callback(create_enemy("Fire giant (level 86)")?)?.ok_or("Missing DHCB step")?;
+    assert_eq!(after, before);
+    Ok(())
+}
+
 #[test]
 fn test_dragon_hunter_crossbow_max_hit() -> TResult<()> {
     let mut player = PlayerConstructor::new()
@@ -132,7 +169,7 @@ fn test_dragon_hunter_crossbow_max_hit() -> TResult<()> {
         .equip("Dragon bolts")?
         .activate_prayer("Rigour")?
         .build();
-    player.change_combat_style(1)?;
+    player.select_combat_style("Rapid")?;
     let enemy = create_enemy("Mithril dragon")?;
     assert_eq!(player.max_hit(enemy), 46.into());
     Ok(())
@@ -145,7 +182,7 @@ fn test_dragon_hunter_crossbow_dps() -> TResult<()> {
         .equip("Dragon bolts")?
         .activate_prayer("Rigour")?
         .build();
-    player.change_combat_style(1)?;
+    player.select_combat_style("Rapid")?;
     let enemy = create_enemy("Mithril dragon")?;
     assert_float_eq(player.dps(enemy), 2.340_311_149_659_705);
     Ok(())
@@ -165,7 +202,7 @@ fn test_colossal_blade_dps() -> TResult<()> {
 #[test]
 fn test_wind_bolt_dps() -> TResult<()> {
     let player = PlayerConstructor::new().select_spell("Wind Bolt")?.build();
-    let enemy = create_enemy("Fire giant (level 86)").unwrap();
+    let enemy = create_enemy("Fire giant (level 86)")?;
     assert_float_eq(player.dps(enemy), 1.430_348_618_544_771);
     Ok(())
 }
@@ -180,3 +217,1595 @@ fn test_trident_of_the_swamp() -> TResult<()> {
     assert_float_eq(player.dps(enemy), 2.141_780_355_389_947_5);
     Ok(())
 }
+
+#[test]
+fn test_manual_cast_with_any_weapon() -> TResult<()> {
+    let player = PlayerConstructor::new()
+        .equip("Abyssal whip")?
+        .select_spell("Wind Bolt")?
+        .build();
+    let enemy = create_enemy("Fire giant (level 86)")?;
+    assert_eq!(player.style_type(), StyleType::Magic);
+    assert_float_eq(player.dps(enemy), 1.430_348_618_544_771);
+    Ok(())
+}
+
+#[test]
+fn test_autocast_requires_capable_weapon() -> TResult<()> {
+    assert!(PlayerConstructor::new()
+        .equip("Abyssal whip")?
+        .autocast_spell("Wind Bolt")
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn test_staff_autocast() -> TResult<()> {
+    let mut player = PlayerConstructor::new()
+        .equip("Staff of air")?
+        .autocast_spell("Wind Bolt")?
+        .build();
+    let enemy = create_enemy("Fire giant (level 86)")?;
+    assert!(player.is_casting());
+    assert_eq!(player.max_accuracy_roll(enemy), 7992.into());
+    assert_eq!(player.max_hit(enemy), 9.into());
+
+    player.select_combat_style("Bash")?;
+    assert!(!player.is_casting());
+    assert_eq!(player.style_type(), StyleType::Crush);
+    Ok(())
+}
+
+#[test]
+fn test_spell_requires_spellbook() -> TResult<()> {
+    let barrage = GameData::bundled().spell("Ice Barrage")?;
+    assert_eq!(
+        Player::default().select_spell(barrage).err(),
+        Some(Error::WrongSpellbook {
+            spell: "Ice Barrage".to_owned(),
+            spellbook: Spellbook::Standard,
+        })
+    );
+    let player = Player::default()
+        .set_spellbook(Spellbook::Ancient)
+        .select_spell(barrage)?;
+    assert!(player.is_casting());
+    Ok(())
+}
+
+#[test]
+fn test_enemy_magic_defence_modes() -> TResult<()> {
+    let mut enemy = create_enemy("Fire giant (level 86)")?.clone();
+    assert_eq!(enemy.max_defence_roll(&StyleType::Magic), 640.into());
+    enemy.magic_defence = MagicDefence::Defence;
+    assert_eq!(enemy.max_defence_roll(&StyleType::Magic), 4736.into());
+    enemy.magic_defence = MagicDefence::Blend {
+        magic: Fraction::new(1, 2),
+        defence: Fraction::new(1, 2),
+    };
+    assert_eq!(enemy.max_defence_roll(&StyleType::Magic), 2624.into());
+
+    let verzik = create_enemy("Verzik Vitur (Phase 2)")?;
+    assert!(matches!(verzik.magic_defence, MagicDefence::Defence));
+    assert_eq!(verzik.magic_defence_level(), verzik.levels.defence);
+    assert_eq!(verzik.max_defence_roll(&StyleType::Magic), 28006.into());
+    let ice_demon = create_enemy("Ice demon")?;
+    assert_eq!(ice_demon.max_defence_roll(&StyleType::Magic), 6996.into());
+    Ok(())
+}
+
+#[test]
+fn test_player_magic_defence() -> TResult<()> {
+    let player = PlayerConstructor::new().build();
+    assert_eq!(player.max_magic_defence_roll(), 6784.into());
+    let player = PlayerConstructor::new()
+        .activate_prayer("Mystic Might")?
+        .build();
+    assert_eq!(player.max_magic_defence_roll(), 7424.into());
+    Ok(())
+}
+
+#[test]
+fn test_elemental_weakness() -> TResult<()> {
+    let enemy = create_enemy("Ice demon")?;
+    let player = PlayerConstructor::new().select_spell("Fire Bolt")?.build();
+    assert_eq!(player.max_accuracy_roll(enemy), 11059.into());
+    assert_eq!(player.max_hit(enemy), 19.into());
+
+    let player = PlayerConstructor::new().select_spell("Water Bolt")?.build();
+    assert_eq!(player.max_accuracy_roll(enemy), 6912.into());
+    assert_eq!(player.max_hit(enemy), 10.into());
+    Ok(())
+}
+
+#[test]
+fn test_spell_elements() -> TResult<()> {
+    for spell in GameData::bundled().spells().values() {
+        let expected = [
+            ("Wind ", Element::Air),
+            ("Water ", Element::Water),
+            ("Earth ", Element::Earth),
+            ("Fire ", Element::Fire),
+        ]
+        .into_iter()
+        .find(|(prefix, _)| spell.name.starts_with(prefix))
+        .map(|(_, element)| element);
+        assert_eq!(spell.element, expected, "{}", spell.name);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_barrage_area_dps() -> TResult<()> {
+    let barrage = GameData::bundled().spell("Ice Barrage")?;
+    let player = Player::default()
+        .set_spellbook(Spellbook::Ancient)
+        .select_spell(barrage)?;
+    let giant = create_enemy("Fire giant (level 86)")?;
+    let dragon = create_enemy("Mithril dragon")?;
+    assert_eq!(player.max_targets(), 9);
+    assert_float_eq(player.area_dps_against(giant, 3), 3.0 * player.dps(giant));
+    assert_float_eq(player.area_dps_against(giant, 12), 9.0 * player.dps(giant));
+    assert_float_eq(
+        player.area_dps(&[giant, dragon]),
+        player.dps(giant) + player.dps(dragon),
+    );
+    Ok(())
+}
+
+#[test]
+fn test_burst_area_dps() -> TResult<()> {
+    let giant = create_enemy("Fire giant (level 86)")?;
+    let dragon = create_enemy("Mithril dragon")?;
+    for name in ["Smoke Burst", "Ice Burst"] {
+        let player = Player::default()
+            .set_spellbook(Spellbook::Ancient)
+            .select_spell(GameData::bundled().spell(name)?)?;
+        assert_eq!(player.area_radius(), Some(1.into()));
+        assert_eq!(player.max_targets(), 9);
+
+        // Only the first nine targets are hit, in the order given
+        let mut targets = vec![giant; 9];
+        targets.push(dragon);
+        assert_float_eq(player.area_dps(&targets), 9.0 * player.dps(giant));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_single_target_area_dps() -> TResult<()> {
+    let player = PlayerConstructor::new().equip("Abyssal whip")?.build();
+    let giant = create_enemy("Fire giant (level 86)")?;
+    let dragon = create_enemy("Mithril dragon")?;
+    assert_eq!(player.max_targets(), 1);
+    assert_float_eq(player.area_dps(&[giant, dragon]), player.dps(giant));
+    Ok(())
+}
+
+#[test]
+fn test_scythe_hits_by_enemy_size() -> TResult<()> {
+    let player = PlayerConstructor::new()
+        .equip("Scythe of vitur")?
+        .activate_prayer("Piety")?
+        .build();
+    let max_hits = |enemy| -> TResult<Vec<i32>> {
+        Ok(player
+            .attack_result(create_enemy(enemy)?)
+            .hitsplats
+            .iter()
+            .map(|hitsplat| hitsplat.range.max.into())
+            .collect())
+    };
+    assert_eq!(max_hits("Fire giant (level 86)")?, vec![28, 14]);
+    assert_eq!(max_hits("Mithril dragon")?, vec![28, 14, 7]);
+    Ok(())
+}
+
+#[test]
+fn test_osmumtens_fang() -> TResult<()> {
+    let player = PlayerConstructor::new()
+        .equip("Osmumten's fang")?
+        .activate_prayer("Piety")?
+        .build();
+    let enemy = create_enemy("Mithril dragon")?;
+    let hitsplats = player.attack_result(enemy).hitsplats;
+    assert_eq!(hitsplats.len(), 1);
+    assert_eq!(hitsplats[0].range.min, 5.into());
+    assert_eq!(hitsplats[0].range.max, 29.into());
+    let single_roll = hit_chance(
+        player.max_accuracy_roll(enemy),
+        enemy.max_defence_roll(&StyleType::Stab),
+    );
+    assert!(hitsplats[0].accuracy > single_roll);
+    Ok(())
+}
+
+#[test]
+fn test_dark_bow_hits_twice() -> TResult<()> {
+    let player = PlayerConstructor::new()
+        .equip("Dark bow")?
+        .equip("Dragon arrow")?
+        .build();
+    let enemy = create_enemy("Fire giant (level 86)")?;
+    let result = player.attack_result(enemy);
+    assert_eq!(result.hitsplats.len(), 2);
+    assert_eq!(result.hitsplats[0], result.hitsplats[1]);
+    assert_eq!(result.hitsplats[0].range.max, player.max_hit(enemy));
+    Ok(())
+}
+
+#[test]
+fn test_dark_bow_special_attack() -> TResult<()> {
+    // Each arrow rolls up to the max hit, then the multiplied roll is clamped to the minimum and cap
+    let clamped_average = |max_hit: i32, dividend: i32, divisor: i32, min_hit: i32| {
+        let total: i32 = (0..=max_hit)
+            .map(|roll| (roll * dividend / divisor).clamp(min_hit, 48))
+            .sum();
+        f64::from(total) / f64::from(max_hit + 1)
+    };
+
+    let enemy = create_enemy("Fire giant (level 86)")?;
+    let player = PlayerConstructor::new()
+        .equip("Dark bow")?
+        .equip("Dragon arrow")?
+        .build()
+        .use_special_attack(true);
+    let max_hit = *player.max_hit(enemy);
+    let result = player.attack_result(enemy);
+    assert_eq!(result.hitsplats.len(), 2);
+    let hitsplat = result.hitsplats[0];
+    assert_eq!(hitsplat.range.min, 8.into());
+    assert_eq!(hitsplat.range.max, min(48, max_hit * 3 / 2).into());
+    assert_eq!(hitsplat.on_miss, MissDamage::Minimum);
+    let average = clamped_average(max_hit, 3, 2, 8);
+    // Rolls below the minimum all deal the minimum, which a uniform range would overstate
+    assert!(average < f64::from(8 + *hitsplat.range.max) / 2.0);
+    assert_float_eq(hitsplat.range.average(), average);
+    assert_float_eq(
+        result.expected_damage(),
+        2.0 * (hitsplat.accuracy * average + (1.0 - hitsplat.accuracy) * 8.0),
+    );
+
+    let player = PlayerConstructor::new()
+        .equip("Dark bow")?
+        .equip("Amethyst arrow")?
+        .build()
+        .use_special_attack(true);
+    let max_hit = *player.max_hit(enemy);
+    let hitsplat = player.attack_result(enemy).hitsplats[0];
+    assert_eq!(hitsplat.range.min, 5.into());
+    assert_eq!(hitsplat.range.max, min(48, max_hit * 13 / 10).into());
+    assert_float_eq(
+        hitsplat.range.average(),
+        clamped_average(max_hit, 13, 10, 5),
+    );
+
+    // Rolls above the cap all deal the cap
+    let modifier = HitRangeModifier {
+        damage: Some(Fraction::new(3, 2)),
+        min_hit: 8.into(),
+        max_hit_cap: Some(48.into()),
+        on_miss: MissDamage::Minimum,
+    };
+    let range = modifier.apply(60.into());
+    assert_eq!((range.min, range.max), (8.into(), 48.into()));
+    assert_float_eq(range.average(), clamped_average(60, 3, 2, 8));
+    assert!(range.average() > f64::from(8 + 48) / 2.0);
+    Ok(())
+}
+
+#[test]
+fn test_ruby_bolt_proc() -> TResult<()> {
+    let mut player = PlayerConstructor::new()
+        .equip("Dragon hunter crossbow")?
+        .equip("Ruby dragon bolts (e)")?
+        .activate_prayer("Rigour")?
+        .build();
+    player.select_combat_style("Rapid")?;
+    let enemy = create_enemy("Mithril dragon")?;
+    let result = player.attack_result(enemy);
+    let proc_effect = result.proc_effect.clone().ok_or("Ruby bolts should proc")?;
+    assert_float_eq(proc_effect.chance, 0.06);
+    assert_eq!(proc_effect.hitsplats[0].range.max, 50.into());
+    assert_float_eq(
+        result.expected_damage(),
+        0.94 * result.hitsplats[0].expected_damage() + 0.06 * 50.0,
+    );
+
+    player.extra.kandarin_hard_diary = true;
+    let proc_effect = player.attack_result(enemy).proc_effect.ok_or("Proc")?;
+    assert_float_eq(proc_effect.chance, 0.066);
+    Ok(())
+}
+
+#[test]
+fn test_bolt_proc_effects() -> TResult<()> {
+    let enemy = create_enemy("Mithril dragon")?;
+    let proc_max_hit = |bolts| -> TResult<Option<i32>> {
+        let mut player = PlayerConstructor::new()
+            .equip("Dragon hunter crossbow")?
+            .equip(bolts)?
+            .build();
+        player.select_combat_style("Rapid")?;
+        Ok(player
+            .attack_result(enemy)
+            .proc_effect
+            .map(|proc_effect| proc_effect.hitsplats[0].range.max.into()))
+    };
+    // Regular max hit of 38 against dragons
+    assert_eq!(proc_max_hit("Dragon bolts")?, None);
+    assert_eq!(proc_max_hit("Diamond dragon bolts (e)")?, Some(43));
+    assert_eq!(proc_max_hit("Onyx dragon bolts (e)")?, Some(45));
+    assert_eq!(proc_max_hit("Opal dragon bolts (e)")?, Some(47));
+    assert_eq!(proc_max_hit("Dragonstone dragon bolts (e)")?, None);
+    Ok(())
+}
+
+#[test]
+fn test_zaryte_crossbow_bolt_effects() -> TResult<()> {
+    let enemy = create_enemy("Mithril dragon")?;
+    let player = PlayerConstructor::new()
+        .equip("Zaryte crossbow")?
+        .equip("Ruby dragon bolts (e)")?
+        .build();
+    let proc_effect = player.attack_result(enemy).proc_effect.ok_or("Proc")?;
+    assert_eq!(proc_effect.hitsplats[0].range.max, 55.into());
+
+    let player = PlayerConstructor::new()
+        .equip("Dragon hunter crossbow")?
+        .equip("Ruby dragon bolts (e)")?
+        .build();
+    let proc_effect = player.attack_result(enemy).proc_effect.ok_or("Proc")?;
+    assert_eq!(proc_effect.hitsplats[0].range.max, 50.into());
+    Ok(())
+}
+
+#[test]
+fn test_ammunition_requires_compatible_weapon() -> TResult<()> {
+    let player = PlayerConstructor::new()
+        .equip("Abyssal whip")?
+        .equip("Dragon arrow")?
+        .build();
+    assert!(player.equipped().ammunition_compatible());
+    assert_eq!(player.equipped().total_stats().damage.ranged, 0.into());
+
+    let player = PlayerConstructor::new()
+        .equip("Dark bow")?
+        .equip("Dragon bolts")?
+        .build();
+    assert!(!player.equipped().ammunition_compatible());
+    assert_eq!(player.equipped().total_stats().damage.ranged, 0.into());
+    Ok(())
+}
+
+#[test]
+fn test_ammunition_tier() -> TResult<()> {
+    let player = PlayerConstructor::new()
+        .equip("Magic shortbow")?
+        .equip("Dragon arrow")?
+        .build();
+    assert!(!player.equipped().ammunition_compatible());
+    assert_eq!(player.equipped().total_stats().damage.ranged, 0.into());
+
+    let player = PlayerConstructor::new()
+        .equip("Magic shortbow")?
+        .equip("Amethyst arrow")?
+        .build();
+    assert!(player.equipped().ammunition_compatible());
+    assert_eq!(player.equipped().total_stats().damage.ranged, 55.into());
+    Ok(())
+}
+
+#[test]
+fn test_blowpipe_uses_loaded_darts() -> TResult<()> {
+    let mut player = PlayerConstructor::new()
+        .equip("Toxic blowpipe")?
+        .equip("Dragon dart")?
+        .build();
+    assert_eq!(player.equipped().total_stats().damage.ranged, 20.into());
+
+    player = PlayerConstructor { player }
+        .load_ammunition("Dragon dart")?
+        .build();
+    assert_eq!(player.equipped().total_stats().damage.ranged, 55.into());
+    assert!(PlayerConstructor::new()
+        .equip("Abyssal whip")?
+        .load_ammunition("Dragon dart")
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn test_blowpipe_attack_speed_profile() -> TResult<()> {
+    let mut player = PlayerConstructor::new()
+        .equip("Toxic blowpipe")?
+        .load_ammunition("Amethyst dart")?
+        .build();
+    player.select_combat_style("Rapid")?;
+    let wielded = player.equipped().wielded;
+    assert_eq!(
+        wielded.attack_speed(player.combat_option(), false)?,
+        2.into()
+    );
+    assert_eq!(
+        wielded.attack_speed(player.combat_option(), true)?,
+        3.into()
+    );
+    Ok(())
+}
+
+#[test]
+fn test_chinchompa_accuracy_by_distance() -> TResult<()> {
+    let mut player = PlayerConstructor::new().equip("Black chinchompa")?.build();
+    let enemy = create_enemy("Fire giant (level 86)")?;
+    let mut accuracy = |style, distance: i32| -> TResult<i32> {
+        player.change_combat_style(style)?;
+        player.extra.distance = distance.into();
+        Ok(player.max_accuracy_roll(enemy).into())
+    };
+    assert_eq!(accuracy(0, 1)?, 15840);
+    assert_eq!(accuracy(1, 1)?, 11556);
+    assert_eq!(accuracy(2, 1)?, 7704);
+    assert_eq!(accuracy(0, 5)?, 11880);
+    assert_eq!(accuracy(1, 5)?, 15408);
+    assert_eq!(accuracy(2, 7)?, 15408);
+    Ok(())
+}
+
+#[test]
+fn test_attack_range_and_approach() -> TResult<()> {
+    let enemy = create_enemy("Fire giant (level 86)")?;
+    let mut player = PlayerConstructor::new().equip("Abyssal whip")?.build();
+    player.extra.distance = 5.into();
+    assert!(!player.can_reach());
+    assert_eq!(player.approach_ticks(enemy), 2.into());
+    player.extra.distance = 0.into();
+    assert!(!player.can_reach());
+    assert_eq!(player.approach_ticks(enemy), 1.into());
+
+    let mut player = PlayerConstructor::new().equip("Magic shortbow")?.build();
+    player.extra.distance = 9.into();
+    assert_eq!(player.attack_range(), 7.into());
+    assert_eq!(player.approach_ticks(enemy), 1.into());
+    player.select_combat_style("Longrange")?;
+    assert_eq!(player.attack_range(), 9.into());
+    assert!(player.can_reach());
+    assert_eq!(player.approach_ticks(enemy), 0.into());
+    Ok(())
+}
+
+#[test]
+fn test_kill_time_includes_approach() -> TResult<()> {
+    let enemy = create_enemy("Fire giant (level 86)")?;
+    let mut player = PlayerConstructor::new().equip("Abyssal whip")?.build();
+    let kill_time = player.kill_time(enemy);
+    assert_float_eq(kill_time, 111.0 / player.dps(enemy));
+    player.extra.distance = 4.into();
+    assert_float_eq(player.kill_time(enemy), kill_time + 1.2);
+    Ok(())
+}
+
+#[test]
+fn test_attack_speed() -> TResult<()> {
+    let enemy = create_enemy("Fire giant (level 86)")?;
+    let player = PlayerConstructor::new().select_spell("Wind Bolt")?.build();
+    assert_eq!(player.attack_speed(enemy), 5.into());
+
+    let mut player = PlayerConstructor::new()
+        .equip("Trident of the swamp")?
+        .build();
+    assert_eq!(player.attack_speed(enemy), 4.into());
+    player.select_combat_style("Longrange")?;
+    assert_eq!(player.attack_speed(enemy), 4.into());
+
+    let mut player = PlayerConstructor::new().equip("Magic shortbow")?.build();
+    assert_eq!(player.attack_speed(enemy), 4.into());
+    player.select_combat_style("Rapid")?;
+    assert_eq!(player.attack_speed(enemy), 3.into());
+    Ok(())
+}
+
+#[test]
+fn test_harmonised_nightmare_staff_attack_speed() -> TResult<()> {
+    let enemy = create_enemy("Fire giant (level 86)")?;
+    let player = PlayerConstructor::new()
+        .equip("Harmonised nightmare staff")?
+        .autocast_spell("Wind Bolt")?
+        .build();
+    assert_eq!(player.attack_speed(enemy), 4.into());
+
+    let player = Player::default()
+        .equip(GameData::bundled().item("Harmonised nightmare staff")?)
+        .set_spellbook(Spellbook::Ancient)
+        .select_spell(GameData::bundled().spell("Ice Barrage")?)?;
+    assert_eq!(player.attack_speed(enemy), 5.into());
+    Ok(())
+}
+
+#[test]
+fn test_spell_specific_attack_speed() -> TResult<()> {
+    let spells = r#"[
+    {
+        "name": "Slow Bolt",
+        "max_hit": 9,
+        "spellbook": "Standard",
+        "attributes": ["Bolt"],
+        "element": "Air",
+        "attack_speed": 6
+    }
+]"#;
+    let data = GameData::bundled().layered(DataFiles {
+        spells: Some(DataFile {
+            path: "slow-spells.json",
+            contents: spells,
+        }),
+        ..DataFiles::default()
+    })?;
+    let enemy = create_enemy("Fire giant (level 86)")?;
+    let player = Player::default().select_spell(data.spell("Slow Bolt")?)?;
+    assert_eq!(player.attack_speed(enemy), 6.into());
+
+    let player = Player::default().select_spell(data.spell("Wind Bolt")?)?;
+    assert_eq!(player.attack_speed(enemy), 5.into());
+    Ok(())
+}
+
+#[test]
+fn test_wilderness_weapon_bonus() -> TResult<()> {
+    let enemy = create_enemy("Fire giant (level 86)")?;
+    let mut player = PlayerConstructor::new().equip("Craw's bow")?.build();
+    let max_hit = player.max_hit(enemy);
+    let accuracy_roll = player.max_accuracy_roll(enemy);
+
+    player.extra.in_wilderness = true;
+    assert_eq!(player.max_hit(enemy), max_hit * Fraction::new(3, 2));
+    assert_eq!(
+        player.max_accuracy_roll(enemy),
+        accuracy_roll * Fraction::new(3, 2)
+    );
+
+    player.extra.wilderness_weapon_charged = false;
+    assert_eq!(player.max_hit(enemy), max_hit);
+    assert_eq!(player.max_accuracy_roll(enemy), accuracy_roll);
+
+    player.extra.wilderness_weapon_charged = true;
+    player.extra.pvp = true;
+    assert_eq!(player.max_hit(enemy), max_hit);
+    assert_eq!(player.max_accuracy_roll(enemy), accuracy_roll);
+    Ok(())
+}
+
+#[test]
+fn test_black_mask_requires_slayer_task() -> TResult<()> {
+    let giant = create_enemy("Fire giant (level 86)")?;
+    let dragon = create_enemy("Mithril dragon")?;
+    let player = PlayerConstructor::new()
+        .equip("Abyssal whip")?
+        .equip("Black mask")?
+        .build();
+    let giant_max_hit = player.max_hit(giant);
+    let dragon_max_hit = player.max_hit(dragon);
+    assert!(!player.on_slayer_task(giant));
+
+    let player = player.assign_slayer_task(Some(SlayerCategory::FireGiants));
+    assert!(player.on_slayer_task(giant));
+    assert!(!player.on_slayer_task(dragon));
+    assert_eq!(player.max_hit(giant), giant_max_hit * Fraction::new(7, 6));
+    assert_eq!(player.max_hit(dragon), dragon_max_hit);
+    Ok(())
+}
+
+#[test]
+fn test_imbued_slayer_helmet_boosts_ranged_on_task() -> TResult<()> {
+    let giant = create_enemy("Fire giant (level 86)")?;
+    let player = PlayerConstructor::new()
+        .equip("Magic shortbow")?
+        .equip("Amethyst arrow")?
+        .equip("Slayer helmet (i)")?
+        .build();
+    let unboosted = player.max_accuracy_roll(giant);
+    let player = player.assign_slayer_task(Some(SlayerCategory::FireGiants));
+    assert_eq!(
+        player.max_accuracy_roll(giant),
+        unboosted * Fraction::new(23, 20)
+    );
+    Ok(())
+}
+
+#[test]
+fn test_salve_amulet_and_black_mask_do_not_stack() -> TResult<()> {
+    let enemy = create_enemy("Vorkath")?;
+    let necks = [
+        None,
+        Some("Salve amulet"),
+        Some("Salve amulet (e)"),
+        Some("Salve amulet(i)"),
+        Some("Salve amulet(ei)"),
+    ];
+    let heads = [None, Some("Black mask"), Some("Slayer helmet (i)")];
+
+    for neck in necks {
+        for head in heads {
+            for style in [StyleType::Slash, StyleType::Ranged, StyleType::Magic] {
+                let mut constructor = match style {
+                    StyleType::Ranged => PlayerConstructor::new()
+                        .equip("Magic shortbow")?
+                        .equip("Amethyst arrow")?,
+                    StyleType::Magic => PlayerConstructor::new().select_spell("Fire Bolt")?,
+                    _ => PlayerConstructor::new().equip("Abyssal whip")?,
+                };
+                for item in [neck, head].into_iter().flatten() {
+                    constructor = constructor.equip(item)?;
+                }
+                let player = constructor
+                    .build()
+                    .assign_slayer_task(Some(SlayerCategory::BlueDragons));
+                assert_eq!(player.style_type(), style);
+                let breakdown = player.explain(enemy)?;
+
+                let melee = style == StyleType::Slash;
+                let salve = match neck {
+                    Some("Salve amulet") if melee => Some(Fraction::new(7, 6)),
+                    Some("Salve amulet (e)") if melee => Some(Fraction::new(6, 5)),
+                    Some("Salve amulet(i)") if style == StyleType::Magic => {
+                        Some(Fraction::new(23, 20))
+                    }
+                    Some("Salve amulet(i)") => Some(Fraction::new(7, 6)),
+                    Some("Salve amulet(ei)") => Some(Fraction::new(6, 5)),
+                    _ => None,
+                };
+                let mask = match head {
+                    Some("Black mask" | "Slayer helmet (i)") if melee => Some(Fraction::new(7, 6)),
+                    Some("Slayer helmet (i)") => Some(Fraction::new(23, 20)),
+                    _ => None,
+                };
+                let context = format!("{neck:?} with {head:?}, {style:?}");
+
+                // Only the bonus that takes precedence changes the roll, the others pass it on
+                let mut sides = vec![("accuracy", &breakdown.accuracy_roll_steps)];
+                if style != StyleType::Magic {
+                    sides.push(("max hit", &breakdown.max_hit_steps));
+                }
+                for (side, steps) in sides {
+                    let changed: Vec<_> = steps
+                        .iter()
+                        .filter(|step| matches!(step.kind, StepKind::Callback(_)) && step.changed())
+                        .collect();
+                    match salve.or(mask) {
+                        Some(factor) => {
+                            assert_eq!(changed.len(), 1, "{side}, {context}");
+                            assert_eq!(
+                                changed[0].after,
+                                changed[0].before * factor,
+                                "{side}, {context}"
+                            );
+                        }
+                        None => assert!(changed.is_empty(), "{side}, {context}"),
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_explain_breakdown() -> TResult<()> {
+    let enemy = create_enemy("Fire giant (level 86)")?;
+    let player = PlayerConstructor::new()
+        .equip("Abyssal whip")?
+        .equip("Black mask")?
+        .activate_prayer("Piety")?
+        .build()
+        .assign_slayer_task(Some(SlayerCategory::FireGiants));
+    let breakdown = player.explain(enemy)?;
+
+    assert_eq!(breakdown.accuracy_roll, player.max_accuracy_roll(enemy));
+    assert_eq!(breakdown.max_hit, player.max_hit(enemy));
+    assert_float_eq(breakdown.dps, player.dps(enemy));
+
+    let kinds: Vec<StepKind> = breakdown
+        .max_hit_steps
+        .iter()
+        .map(|step| step.kind)
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            StepKind::Level,
+            StepKind::Prayer,
+            StepKind::InvisibleBoost,
+            StepKind::EffectiveLevel,
+            StepKind::EquipmentBonus,
+            StepKind::Callback(Attribute::BlackMask),
+        ]
+    );
+    let black_mask = breakdown.max_hit_steps[5];
+    assert!(black_mask.changed());
+    assert_eq!(black_mask.after, black_mask.before * Fraction::new(7, 6));
+    assert_eq!(black_mask.after, breakdown.max_hit);
+    for steps in [&breakdown.accuracy_roll_steps, &breakdown.max_hit_steps] {
+        for pair in steps.windows(2) {
+            assert_eq!(pair[0].after, pair[1].before);
+        }
+    }
+    assert_float_eq(
+        breakdown.hit_chance,
+        hit_chance(breakdown.accuracy_roll, breakdown.enemy_defence_roll),
+    );
+
+    // Osmumten's fang rolls accuracy twice
+    let player = PlayerConstructor::new().equip("Osmumten's fang")?.build();
+    let breakdown = player.explain(enemy)?;
+    assert_float_eq(
+        breakdown.hit_chance,
+        double_roll_hit_chance(breakdown.accuracy_roll, breakdown.enemy_defence_roll),
+    );
+    assert_float_eq(
+        breakdown.hit_chance,
+        player.attack_result(enemy).hitsplats[0].accuracy,
+    );
+    Ok(())
+}
+
+#[test]
+fn test_non_attacking_style() -> TResult<()> {
+    let enemy = create_enemy("Fire giant (level 86)")?;
+    let mut player = PlayerConstructor::new().equip("Dinh's bulwark")?.build();
+    assert!(player.try_dps(enemy)? > 0.0);
+
+    player.select_combat_style("Block")?;
+    assert_eq!(player.try_max_hit(enemy), Err(Error::NonAttackingStyle));
+    assert_eq!(player.try_dps(enemy), Err(Error::NonAttackingStyle));
+    assert!(player.explain(enemy).is_err());
+    assert_eq!(player.max_hit(enemy), 0.into());
+    assert_eq!(player.max_accuracy_roll(enemy), 0.into());
+    assert_float_eq(player.dps(enemy), 0.0);
+    assert!(player.kill_time(enemy).is_infinite());
+
+    // A gun is only fired in minigames, so its default style does not attack either
+    let mut player = PlayerConstructor::new().equip("Fixed device")?.build();
+    assert_eq!(player.combat_option().name, "Aim and Fire");
+    assert_eq!(player.try_dps(enemy), Err(Error::NonAttackingStyle));
+    assert_float_eq(player.dps(enemy), 0.0);
+    player.select_combat_style("Kick")?;
+    assert!(player.try_dps(enemy)? > 0.0);
+    Ok(())
+}
+
+#[test]
+fn test_game_data_lookup_suggestions() -> TResult<()> {
+    let Err(error) = GameData::bundled().item("abyssal whp") else {
+        return Err("Found a misspelled item".into());
+    };
+    assert_eq!(error.suggestion.as_deref(), Some("Abyssal whip"));
+    assert_eq!(
+        error.to_string(),
+        "Unknown item `abyssal whp`, did you mean `Abyssal whip`?"
+    );
+    let Err(error) = GameData::bundled().enemy("Jad") else {
+        return Err("Found an unknown enemy".into());
+    };
+    assert_eq!(error.suggestion, None);
+    assert!(GameData::bundled().prayer("Piety").is_ok());
+    assert!(GameData::bundled().spell("Ice barage").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_game_data_reports_problems() -> TResult<()> {
+    let equipment = r#"[
+    {
+        "name": "Broken bow",
+        "slot": "WeaponTwoHanded",
+        "attack": {"stab": 0, "slash": 0, "crush": 0, "ranged": 0, "magic": 0},
+        "defence": {"stab": 0, "slash": 0, "crush": 0, "ranged": 0, "magic": 0},
+        "damage": {"strength": 0, "ranged": 0, "magic": 0},
+        "prayer_bonus": 0,
+        "attributes": []
+    },
+    {
+        "name": "Hat",
+        "slot": "Head",
+        "attack": {"stab": 0, "slash": 0, "crush": 0, "ranged": 0, "magic": 0},
+        "defence": {"stab": 0, "slash": 0, "crush": 0, "ranged": 0, "magic": 0},
+        "damage": {"strength": 0, "ranged": 0, "magic": 0},
+        "prayer_bonus": 0,
+        "attributes": ["Hat"]
+    },
+    {
+        "name": "Hat",
+        "slot": "Head",
+        "attack": {"stab": 0, "slash": 0, "crush": 0, "ranged": 0, "magic": 0},
+        "defence": {"stab": 0, "slash": 0, "crush": 0, "ranged": 0, "magic": 0},
+        "damage": {"strength": 0, "ranged": 0, "magic": 0},
+        "prayer_bonus": 0,
+        "attributes": []
+    },
+    {
+        "name": "Hat",
+        "slot": "Head",
+        "weapon_stats": {"weapon_type": "Bow", "attack_speed": 4, "range": 1},
+        "attack": {"stab": 0, "slash": 0, "crush": 0, "ranged": 0, "magic": 0},
+        "defence": {"stab": 0, "slash": 0, "crush": 0, "ranged": 0, "magic": 0},
+        "damage": {"strength": 0, "ranged": 0, "magic": 0},
+        "prayer_bonus": 0,
+        "attributes": []
+    },
+    {
+        "name": "Hat",
+        "slot": "Head",
+        "attack": {"stab": 0, "slash": 0, "crush": 0, "ranged": 0, "magic": 0},
+        "defence": {"stab": 0, "slash": 0, "crush": 0, "ranged": 0, "magic": 0},
+        "damage": {"strength": 0, "ranged": 0, "magic": 0},
+        "prayer_bonus": 0,
+        "attributes": []
+    }
+]"#;
+    let file = |path, contents| DataFile { path, contents };
+    let Err(error) = GameData::from_files(
+        file("equipment.json", equipment),
+        file("enemies.json", "[]"),
+        file("prayers.json", "[]"),
+        file("spells.json", "[}"),
+    ) else {
+        return Err("Invalid data was accepted".into());
+    };
+    let errors = error.errors;
+
+    let summary: Vec<(&str, Option<usize>, Option<&str>)> = errors
+        .iter()
+        .map(|error| (error.file.as_str(), error.line, error.name.as_deref()))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("equipment.json", Some(2), Some("Broken bow")),
+            ("equipment.json", Some(11), Some("Hat")),
+            ("equipment.json", Some(29), Some("Hat")),
+            ("equipment.json", Some(39), Some("Hat")),
+            ("spells.json", Some(1), None),
+        ]
+    );
+    assert_eq!(
+        errors[0].kind,
+        DataErrorKind::Invalid("weapon without `weapon_stats`".to_owned())
+    );
+    assert!(
+        matches!(&errors[1].kind, DataErrorKind::Parse(message) if message.contains("unknown variant `Hat`"))
+    );
+    assert!(errors[2]
+        .to_string()
+        .starts_with("equipment.json:29 (Hat): `weapon_stats`"));
+    assert_eq!(errors[3].kind, DataErrorKind::Duplicate { first_line: 20 });
+    Ok(())
+}
+
+#[test]
+fn test_bundled_data_matches_data_directory() -> TResult<()> {
+    let loaded = GameData::load("./data")?;
+    assert_eq!(loaded.items().len(), GameData::bundled().items().len());
+    assert_eq!(loaded.enemies().len(), GameData::bundled().enemies().len());
+    assert_eq!(loaded.prayers().len(), GameData::bundled().prayers().len());
+    assert_eq!(loaded.spells().len(), GameData::bundled().spells().len());
+
+    let layered = GameData::bundled().layered_from("./does-not-exist")?;
+    assert_eq!(layered.items().len(), GameData::bundled().items().len());
+    Ok(())
+}
+
+#[test]
+fn test_layered_data_replaces_entries() -> TResult<()> {
+    let equipment = r#"[
+    {
+        "name": "Abyssal whip",
+        "slot": "WeaponOneHanded",
+        "weapon_stats": {"weapon_type": "Whip", "attack_speed": 4, "range": 1},
+        "attack": {"stab": 0, "slash": 200, "crush": 0, "ranged": 0, "magic": 0},
+        "defence": {"stab": 0, "slash": 0, "crush": 0, "ranged": 0, "magic": 0},
+        "damage": {"strength": 82, "ranged": 0, "magic": 0},
+        "prayer_bonus": 0,
+        "attributes": []
+    }
+]"#;
+    let layered = GameData::bundled().layered(DataFiles {
+        equipment: Some(DataFile {
+            path: "overrides.json",
+            contents: equipment,
+        }),
+        ..DataFiles::default()
+    })?;
+
+    assert_eq!(layered.items().len(), GameData::bundled().items().len());
+    assert_eq!(
+        layered.item("Abyssal whip")?.inner().stats.attack.slash,
+        200.into()
+    );
+    assert_eq!(
+        GameData::bundled()
+            .item("Abyssal whip")?
+            .inner()
+            .stats
+            .attack
+            .slash,
+        82.into()
+    );
+    let origin = layered
+        .item_origin("Abyssal whip")
+        .ok_or("Missing origin")?;
+    assert_eq!((origin.file.as_str(), origin.line), ("overrides.json", 2));
+    assert_eq!(
+        layered.item_origin("Dark bow"),
+        GameData::bundled().item_origin("Dark bow")
+    );
+    Ok(())
+}
+
+#[test]
+fn test_overrides_patch_entries_field_by_field() -> TResult<()> {
+    let equipment = r#"[
+    {"name": "Abyssal whip", "attack": {"slash": 100}},
+    {
+        "name": "Training whip",
+        "slot": "WeaponOneHanded",
+        "weapon_stats": {"weapon_type": "Whip", "attack_speed": 4, "range": 1},
+        "attack": {"stab": 0, "slash": 10, "crush": 0, "ranged": 0, "magic": 0},
+        "defence": {"stab": 0, "slash": 0, "crush": 0, "ranged": 0, "magic": 0},
+        "damage": {"strength": 10, "ranged": 0, "magic": 0},
+        "prayer_bonus": 0,
+        "attributes": []
+    }
+]"#;
+    let patched = GameData::bundled().with_overrides(DataFiles {
+        equipment: Some(DataFile {
+            path: "overrides.json",
+            contents: equipment,
+        }),
+        ..DataFiles::default()
+    })?;
+
+    assert_eq!(patched.items().len(), GameData::bundled().items().len() + 1);
+    let whip = patched.item("Abyssal whip")?.inner();
+    assert_eq!(whip.stats.attack.slash, 100.into());
+    assert_eq!(whip.stats.damage.strength, 82.into());
+    assert_eq!(
+        GameData::bundled()
+            .item("Abyssal whip")?
+            .inner()
+            .stats
+            .attack
+            .slash,
+        82.into()
+    );
+    assert_eq!(
+        patched.item("Training whip")?.inner().stats.attack.slash,
+        10.into()
+    );
+    let origin = patched
+        .item_origin("Abyssal whip")
+        .ok_or("Missing origin")?;
+    assert_eq!((origin.file.as_str(), origin.line), ("overrides.json", 2));
+    assert!(GameData::bundled()
+        .with_overrides_from("./does-not-exist")
+        .is_ok());
+    Ok(())
+}
+
+#[test]
+fn test_overrides_report_conflicts() -> TResult<()> {
+    let equipment = r#"[
+    {"name": "Abyssal whip", "attack": {"slash": 100}},
+    {"name": "Abyssal whip", "attack": {"slash": 120, "stab": 5}},
+    {"name": "Dark bow", "weapon_stats": 5}
+]"#;
+    let Err(error) = GameData::bundled().with_overrides(DataFiles {
+        equipment: Some(DataFile {
+            path: "overrides.json",
+            contents: equipment,
+        }),
+        ..DataFiles::default()
+    }) else {
+        return Err("Conflicting overrides were accepted".into());
+    };
+    let errors = error.errors;
+
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].line, Some(3));
+    assert_eq!(
+        errors[0].kind,
+        DataErrorKind::Conflict("`attack.slash` is already overridden on line 2".to_owned())
+    );
+    assert_eq!(errors[1].line, Some(4));
+    assert_eq!(
+        errors[1].kind,
+        DataErrorKind::Conflict(
+            "`weapon_stats` cannot change between an object and a value".to_owned()
+        )
+    );
+    Ok(())
+}
+
+#[test]
+fn test_loadout_resolves_into_player() -> TResult<()> {
+    let json = r#"{
+        "gear": {"weapon": "Abyssal whip", "shield": "Dragon defender"},
+        "prayers": ["Piety"],
+        "style": "lash"
+    }"#;
+    let player = Loadout::from_json(json)?.resolve(GameData::bundled())?;
+    let enemy = create_enemy("Fire giant (level 86)")?;
+    assert_eq!(player.combat_option().name, "Lash");
+    assert_eq!(player.max_accuracy_roll(enemy), 21590.into());
+    assert_eq!(player.max_hit(enemy), 31.into());
+
+    let loadout = Loadout::from(&player);
+    assert_eq!(loadout.style.as_deref(), Some("Lash"));
+    assert_eq!(Loadout::from_json(&loadout.to_json()?)?, loadout);
+    Ok(())
+}
+
+#[test]
+fn test_loadout_autocast_and_boosts() -> TResult<()> {
+    let mut loadout = Loadout {
+        spell: Some("Wind Bolt".to_owned()),
+        casting: Casting::Autocast,
+        ..Loadout::default()
+    };
+    loadout.gear.weapon = Some("Staff of air".to_owned());
+    loadout.levels.magic = 90.into();
+    loadout.boosts.magic = 9.into();
+    let player = loadout.resolve(GameData::bundled())?;
+    let enemy = create_enemy("Fire giant (level 86)")?;
+    assert!(player.is_casting());
+    assert_eq!(player.levels.magic, 99.into());
+    assert_eq!(player.max_accuracy_roll(enemy), 7992.into());
+    assert_eq!(Loadout::from(&player).casting, Casting::Autocast);
+
+    // The style is ignored when autocasting, even if the staff has no style by that name
+    loadout.style = Some("Lash".to_owned());
+    let player = loadout.resolve(GameData::bundled())?;
+    assert!(player.combat_option().is_autocast());
+    Ok(())
+}
+
+#[test]
+fn test_loadout_errors() -> TResult<()> {
+    let resolve = |json: &str| -> TResult<LoadoutError> {
+        match Loadout::from_json(json)?.resolve(GameData::bundled()) {
+            Ok(_) => Err("Invalid loadout was resolved".into()),
+            Err(error) => Ok(error),
+        }
+    };
+
+    let error = resolve(r#"{"gear": {"weapon": "Abysal whip"}}"#)?;
+    assert!(matches!(&error, LoadoutError::UnknownName(unknown)
+        if unknown.suggestion.as_deref() == Some("Abyssal whip")));
+    let error = resolve(r#"{"gear": {"head": "Abyssal whip"}}"#)?;
+    assert_eq!(
+        error,
+        LoadoutError::WrongSlot {
+            item: "Abyssal whip".to_owned(),
+            slot: Slot::Head
+        }
+    );
+    assert_eq!(
+        error.to_string(),
+        "Abyssal whip cannot be worn in the head slot"
+    );
+    assert_eq!(
+        resolve(r#"{"gear": {"weapon": "Abyssal whip"}, "style": "Slash"}"#)?.to_string(),
+        "Abyssal whip has no `Slash` style, the options are Flick, Lash, Deflect"
+    );
+    assert!(matches!(
+        resolve(
+            r#"{"gear": {"weapon": "Abyssal whip"}, "spell": "Wind Bolt", "casting": "Autocast"}"#
+        )?,
+        LoadoutError::Incompatible(Error::CannotAutocast { .. })
+    ));
+    assert_eq!(
+        resolve(r#"{"gear": {"weapon": "Scythe of vitur", "shield": "Dragon defender"}}"#)?,
+        LoadoutError::TwoHandedWithShield {
+            weapon: "Scythe of vitur".to_owned(),
+            shield: "Dragon defender".to_owned(),
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn test_select_combat_style() -> TResult<()> {
+    let mut player = PlayerConstructor::new().equip("Abyssal whip")?.build();
+    player.select_combat_style("deflect")?;
+    assert_eq!(player.combat_option().name, "Deflect");
+    player.select_combat_style_by_type(StyleType::Slash, WeaponStyle::Controlled)?;
+    assert_eq!(player.combat_option().name, "Lash");
+    let Err(error) = player.select_combat_style("Slash") else {
+        return Err("Selected a missing style".into());
+    };
+    assert_eq!(
+        error.to_string(),
+        "No `Slash` style, the options are Flick, Lash, Deflect"
+    );
+    assert_eq!(
+        player.select_combat_style_by_type(StyleType::Ranged, WeaponStyle::Rapid),
+        Err(Error::MissingCombatStyle {
+            style_type: StyleType::Ranged,
+            weapon_style: WeaponStyle::Rapid,
+        })
+    );
+    assert_eq!(
+        player.change_combat_style(3),
+        Err(Error::InvalidCombatStyleIndex { index: 3 })
+    );
+
+    let options = player.combat_options();
+    assert_eq!(options.len(), 3);
+    assert_eq!(options[0].0.name, "Flick");
+    assert_eq!(options[0].1.attack, 3.into());
+    assert_eq!(options[1].1.strength, 1.into());
+    assert_eq!(options[2].1.defence, 3.into());
+    Ok(())
+}
+
+#[test]
+fn test_switching_weapons_keeps_equivalent_style() -> TResult<()> {
+    let mut player = PlayerConstructor::new().equip("Abyssal whip")?.build();
+    player.select_combat_style("Deflect")?;
+    let player = player.equip(GameData::bundled().item("Colossal blade")?);
+    assert_eq!(player.combat_option().name, "Block");
+
+    let mut player = player.equip(GameData::bundled().item("Osmumten's fang")?);
+    player.select_combat_style("Lunge")?;
+    let player = player.equip(GameData::bundled().item("Scythe of vitur")?);
+    assert_eq!(player.combat_option().name, "Chop");
+
+    let mut player = player.equip(GameData::bundled().item("Magic shortbow")?);
+    player.select_combat_style("Rapid")?;
+    let player = player.equip(GameData::bundled().item("Dark bow")?);
+    assert_eq!(player.combat_option().name, "Rapid");
+    let player = player.equip(GameData::bundled().item("Abyssal whip")?);
+    assert_eq!(player.combat_option().name, "Flick");
+    Ok(())
+}
+
+#[test]
+fn test_rank_combat_styles() -> TResult<()> {
+    let player = PlayerConstructor::new()
+        .equip("Abyssal whip")?
+        .equip("Dragon defender")?
+        .activate_prayer("Piety")?
+        .build();
+    let enemy = create_enemy("Fire giant (level 86)")?;
+    let ranking = player.rank_combat_styles(enemy);
+    assert_eq!(ranking.len(), 3);
+    assert!(ranking.windows(2).all(|pair| pair[0].dps >= pair[1].dps));
+    let lash = ranking
+        .iter()
+        .find(|evaluation| evaluation.combat_option.name == "Lash")
+        .ok_or("Missing Lash")?;
+    assert_eq!(lash.accuracy_roll, 21590.into());
+    assert_eq!(lash.max_hit, 31.into());
+    assert_eq!(lash.attack_speed, 4.into());
+    assert_float_eq(lash.dps, 5.716_511_895_388_511_5);
+
+    let player = PlayerConstructor::new()
+        .equip("Staff of air")?
+        .autocast_spell("Wind Bolt")?
+        .build();
+    let ranking = player.rank_combat_styles(enemy);
+    assert_eq!(ranking.len(), 5);
+    assert!(ranking[0].combat_option.is_autocast());
+    assert_eq!(ranking[0].max_hit, 9.into());
+
+    let player = PlayerConstructor::new().equip("Staff of air")?.build();
+    assert_eq!(player.rank_combat_styles(enemy).len(), 3);
+
+    let player = PlayerConstructor::new().equip("Dinh's bulwark")?.build();
+    let ranking = player.rank_combat_styles(enemy);
+    assert_eq!(ranking.len(), 1);
+    assert_eq!(ranking[0].combat_option.name, "Pummel");
+
+    let player = PlayerConstructor::new().equip("Osmumten's fang")?.build();
+    let ranking = player.rank_combat_styles(enemy);
+    assert_eq!(ranking.len(), 4);
+    for evaluation in ranking {
+        let defence_roll = enemy.max_defence_roll(&evaluation.combat_option.style_type);
+        assert_float_eq(
+            evaluation.hit_chance,
+            double_roll_hit_chance(evaluation.accuracy_roll, defence_roll),
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn test_optimizer_matches_brute_force() -> TResult<()> {
+    let names = [
+        "Abyssal whip",
+        "Colossal blade",
+        "Osmumten's fang",
+        "Scythe of vitur",
+        "Dragon defender",
+        "Black mask",
+        "Slayer helmet (i)",
+        "Salve amulet",
+        "Salve amulet(ei)",
+    ];
+    let candidates = names
+        .iter()
+        .map(|name| GameData::bundled().item(name))
+        .collect::<Result<Vec<_>, _>>()?;
+    let enemy = create_enemy("Vorkath")?;
+    let base = Player::default().assign_slayer_task(Some(SlayerCategory::BlueDragons));
+
+    let optimized = Optimizer::new(base.clone())
+        .with_candidates(candidates)
+        .optimize(enemy)
+        .ok_or("No loadout found")?;
+
+    let mut best: f64 = 0.0;
+    for weapon in &names[..4] {
+        for shield in [None, Some("Dragon defender")] {
+            for head in ["Black mask", "Slayer helmet (i)"] {
+                for neck in ["Salve amulet", "Salve amulet(ei)"] {
+                    let mut player = base.clone().equip(GameData::bundled().item(weapon)?);
+                    if let Some(shield) = shield {
+                        let shielded = player.clone().equip(GameData::bundled().item(shield)?);
+                        if shielded.equipped().wielded.combat_boost().len()
+                            != player.equipped().wielded.combat_boost().len()
+                        {
+                            continue;
+                        }
+                        player = shielded;
+                    }
+                    let player = player
+                        .equip(GameData::bundled().item(head)?)
+                        .equip(GameData::bundled().item(neck)?);
+                    if let Some(evaluation) = player.rank_combat_styles(enemy).first() {
+                        best = best.max(evaluation.dps);
+                    }
+                }
+            }
+        }
+    }
+    assert_float_eq(optimized.dps, best);
+
+    let gear = Loadout::from(&optimized.player).gear;
+    assert_eq!(gear.neck.as_deref(), Some("Salve amulet(ei)"));
+    if gear.weapon.as_deref() == Some("Colossal blade")
+        || gear.weapon.as_deref() == Some("Scythe of vitur")
+    {
+        assert_eq!(gear.shield, None);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_optimizer_matches_brute_force_at_range() -> TResult<()> {
+    const DARTS: [&str; 2] = ["Amethyst dart", "Dragon dart"];
+    let weapons = [
+        "Magic shortbow",
+        "Dark bow",
+        "Toxic blowpipe",
+        "Dragon hunter crossbow",
+        "Zaryte crossbow",
+        "Staff of air",
+        "Harmonised nightmare staff",
+        "Trident of the swamp",
+    ];
+    let ammunition = [
+        "Amethyst arrow",
+        "Dragon arrow",
+        "Dragon bolts",
+        "Ruby dragon bolts (e)",
+    ];
+    let data = GameData::bundled();
+    let candidates = weapons
+        .iter()
+        .chain(&ammunition)
+        .chain(&DARTS)
+        .map(|name| data.item(name))
+        .collect::<Result<Vec<_>, _>>()?;
+    let base = Player::default().select_spell(data.spell("Fire Bolt")?)?;
+
+    for special_attack in [false, true] {
+        let base = base.clone().use_special_attack(special_attack);
+        for enemy in ["Ice demon", "Vorkath", "Fire giant (level 86)"] {
+            let enemy = create_enemy(enemy)?;
+            let optimized = Optimizer::new(base.clone())
+                .with_candidates(candidates.iter().copied())
+                .optimize(enemy)
+                .ok_or("No loadout found")?;
+
+            let mut best: f64 = 0.0;
+            for weapon in weapons {
+                let player = base.clone().equip(data.item(weapon)?);
+                for ammunition in ammunition.iter().chain(&DARTS) {
+                    let player = player.clone().equip(data.item(ammunition)?);
+                    let darts: &[&str] = if weapon == "Toxic blowpipe" {
+                        &DARTS
+                    } else {
+                        &[]
+                    };
+                    let mut players = vec![player.clone()];
+                    for dart in darts {
+                        players.push(player.clone().load_ammunition(data.item(dart)?)?);
+                    }
+                    for player in players {
+                        if let Some(evaluation) = player.rank_combat_styles(enemy).first() {
+                            best = best.max(evaluation.dps);
+                        }
+                    }
+                }
+            }
+            assert_float_eq(optimized.dps, best);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_optimizer_leaves_slots_empty() -> TResult<()> {
+    // The defender lowers the magic attack bonus, so the best loadout leaves the shield slot empty
+    let data = GameData::bundled();
+    let names = ["Staff of air", "Dragon defender", "Black mask"];
+    let candidates = names
+        .iter()
+        .map(|name| data.item(name))
+        .collect::<Result<Vec<_>, _>>()?;
+    let enemy = create_enemy("Fire giant (level 86)")?;
+    let base = Player::default().select_spell(data.spell("Fire Bolt")?)?;
+
+    let optimized = Optimizer::new(base.clone())
+        .with_candidates(candidates)
+        .optimize(enemy)
+        .ok_or("No loadout found")?;
+
+    let mut best: f64 = 0.0;
+    for weapon in [None, Some("Staff of air")] {
+        for shield in [None, Some("Dragon defender")] {
+            for head in [None, Some("Black mask")] {
+                let mut player = base.clone();
+                for name in [weapon, shield, head].into_iter().flatten() {
+                    player = player.equip(data.item(name)?);
+                }
+                if let Some(evaluation) = player.rank_combat_styles(enemy).first() {
+                    best = best.max(evaluation.dps);
+                }
+            }
+        }
+    }
+    assert_float_eq(optimized.dps, best);
+
+    let gear = Loadout::from(&optimized.player).gear;
+    assert_eq!(gear.weapon.as_deref(), Some("Staff of air"));
+    assert_eq!(gear.shield, None);
+    Ok(())
+}
+
+#[test]
+fn test_optimizer_prunes_stat_only_items() -> TResult<()> {
+    let item = |name: &str, slot: &str, strength: i32, slash: i32| {
+        format!(
+            r#"{{
+        "name": "{name}",
+        "slot": "{slot}",
+        "attack": {{"stab": 0, "slash": {slash}, "crush": 0, "ranged": 0, "magic": 0}},
+        "defence": {{"stab": 0, "slash": 0, "crush": 0, "ranged": 0, "magic": 0}},
+        "damage": {{"strength": {strength}, "ranged": 0, "magic": 0}},
+        "prayer_bonus": 0,
+        "attributes": []
+    }}"#
+        )
+    };
+    let rings = [
+        ("Copper ring", 1, 0),
+        ("Iron ring", 2, 2),
+        ("Steel ring", 8, 0),
+        ("Mithril ring", 0, 30),
+        ("Adamant ring", 4, 4),
+    ];
+    let gloves = [
+        ("Leather gloves", 1, 1),
+        ("Spiked gloves", 6, 0),
+        ("Padded gloves", 0, 12),
+    ];
+    let items: Vec<String> = rings
+        .iter()
+        .map(|(name, strength, slash)| item(name, "Ring", *strength, *slash))
+        .chain(
+            gloves
+                .iter()
+                .map(|(name, strength, slash)| item(name, "Hands", *strength, *slash)),
+        )
+        .collect();
+    let equipment = format!("[{}]", items.join(",\n"));
+    let data = GameData::bundled().layered(DataFiles {
+        equipment: Some(DataFile {
+            path: "accessories.json",
+            contents: &equipment,
+        }),
+        ..DataFiles::default()
+    })?;
+    let enemy = create_enemy("Fire giant (level 86)")?;
+    let base = Player::default()
+        .equip(data.item("Abyssal whip")?)
+        .equip(data.item("Dragon defender")?);
+
+    let optimized = Optimizer::new(base.clone())
+        .with_candidates(
+            data.items()
+                .values()
+                .filter(|item| matches!(item, Slots::Ring(_) | Slots::Hands(_))),
+        )
+        .optimize(enemy)
+        .ok_or("No loadout found")?;
+
+    let mut best: f64 = 0.0;
+    for (ring, _, _) in rings {
+        for (hands, _, _) in gloves {
+            let player = base
+                .clone()
+                .equip(data.item(ring)?)
+                .equip(data.item(hands)?);
+            best = best.max(player.rank_combat_styles(enemy)[0].dps);
+        }
+    }
+    assert_float_eq(optimized.dps, best);
+    // The copper and iron rings are dominated, leaving nine loadouts before bounding
+    assert!(optimized.evaluations < rings.len() * gloves.len());
+    Ok(())
+}
+
+#[test]
+fn test_optimizer_objectives() -> TResult<()> {
+    let candidates = ["Abyssal whip", "Magic shortbow", "Dragon arrow"]
+        .iter()
+        .map(|name| GameData::bundled().item(name))
+        .collect::<Result<Vec<_>, _>>()?;
+    let enemy = create_enemy("Fire giant (level 86)")?;
+    let mut base = Player::default();
+    base.extra.distance = 10.into();
+
+    let by_dps = Optimizer::new(base.clone())
+        .with_candidates(candidates.clone())
+        .optimize(enemy)
+        .ok_or("No loadout found")?;
+    let by_kill_time = Optimizer::new(base)
+        .with_candidates(candidates)
+        .objective(Objective::MinKillTime)
+        .optimize(enemy)
+        .ok_or("No loadout found")?;
+    assert!(by_dps.dps >= by_kill_time.dps);
+    assert!(by_kill_time.kill_time <= by_dps.kill_time);
+
+    let default_player = Player::default();
+    assert!(Optimizer::new(default_player.clone())
+        .optimize(enemy)
+        .is_some());
+    let best_in_slot = Optimizer::new(default_player)
+        .with_all_items(GameData::bundled())
+        .optimize(enemy)
+        .ok_or("No loadout found")?;
+    assert!(best_in_slot.dps >= by_dps.dps);
+    Ok(())
+}
+
+#[test]
+fn test_bank_export() -> TResult<()> {
+    let bank = Bank::from_export(
+        "Item id\tItem name\tItem quantity\n\
+         4151\tAbyssal whip\t1\n\
+         12954\tDragon defender\t0\n\
+         Dragon arrow\t250\n\
+         Osmumten's fang\n",
+    )?;
+    assert!(bank.contains("Abyssal whip"));
+    assert!(bank.contains("Dragon arrow"));
+    assert!(bank.contains("Osmumten's fang"));
+    assert!(!bank.contains("Dragon defender"));
+    assert!(!bank.contains("Item name"));
+    assert_eq!(
+        Bank::from_names(["Abyssal whip"]).with_item("Dragon arrow"),
+        Bank::from_names(["Dragon arrow", "Abyssal whip"])
+    );
+
+    let bank = Bank::from_export("Item id\tItem name\n4151\tAbyssal whip\n12954\tDragon defender")?;
+    assert_eq!(bank, Bank::from_names(["Abyssal whip", "Dragon defender"]));
+
+    let error = |contents: &str| match Bank::from_export(contents) {
+        Ok(_) => Err("Malformed export was accepted"),
+        Err(error) => Ok((error.line, error.reason)),
+    };
+    assert_eq!(
+        error("Abyssal whip\t1\n\n4151\t1")?,
+        (3, "expected `id, name` or `name, quantity`")
+    );
+    assert_eq!(error("Dragon arrow\t250\nAbyssal whip\tsome")?.0, 2);
+    assert_eq!(error("4151\tAbyssal whip\tmany")?.0, 1);
+    assert_eq!(
+        error("Abyssal whip\n4151")?,
+        (2, "an item id without a name")
+    );
+    assert_eq!(error("4151\tAbyssal whip\t1\t0")?, (1, "too many columns"));
+    Ok(())
+}
+
+#[test]
+fn test_optimizer_uses_owned_items_within_budget() -> TResult<()> {
+    let enemy = create_enemy("Vorkath")?;
+    let bank = Bank::from_names(["Abyssal whip", "Dragon defender"]);
+    let prices = Prices::from_json(
+        r#"{
+        "Osmumten's fang": 20000000,
+        "Scythe of vitur": 1000000000,
+        "Salve amulet(ei)": 500000,
+        "Dragon defender": 10000000
+    }"#,
+    )?;
+    let optimizer = Optimizer::new(Player::default()).with_all_items(GameData::bundled());
+
+    let owned = optimizer
+        .clone()
+        .owned(bank.clone())
+        .optimize(enemy)
+        .ok_or("No loadout found")?;
+    let gear = Loadout::from(&owned.player).gear;
+    assert_eq!(gear.weapon.as_deref(), Some("Abyssal whip"));
+    assert_eq!(gear.shield.as_deref(), Some("Dragon defender"));
+    assert_eq!(gear.neck, None);
+    assert_eq!(owned.cost, 0);
+
+    let budgeted = optimizer
+        .clone()
+        .owned(bank.clone())
+        .budget(25_000_000, prices.clone())
+        .optimize(enemy)
+        .ok_or("No loadout found")?;
+    let gear = Loadout::from(&budgeted.player).gear;
+    assert!(budgeted.cost <= 25_000_000);
+    assert_ne!(gear.weapon.as_deref(), Some("Scythe of vitur"));
+    assert_eq!(gear.neck.as_deref(), Some("Salve amulet(ei)"));
+    assert!(budgeted.dps > owned.dps);
+
+    let enemies = [enemy, create_enemy("Fire giant (level 86)")?];
+    let upgrades = optimizer
+        .clone()
+        .owned(bank.clone())
+        .budget(25_000_000, prices.clone())
+        .rank_upgrades(&enemies, &prices);
+    assert!(!upgrades.is_empty());
+    assert!(upgrades
+        .windows(2)
+        .all(|pair| pair[0].dps_per_gp >= pair[1].dps_per_gp));
+    assert!(upgrades.iter().all(|upgrade| upgrade.dps_gain > 0.0
+        && upgrade.name != "Scythe of vitur"
+        && upgrade.name != "Dragon defender"));
+    let salve = upgrades
+        .iter()
+        .find(|upgrade| upgrade.name == "Salve amulet(ei)")
+        .ok_or("Missing salve amulet")?;
+    assert_eq!(salve.price, 500_000);
+    assert_float_eq(salve.dps_per_gp, salve.dps_gain / 500_000.0);
+
+    // Upgrades can be ranked without a budget, and without a bank everything counts as owned
+    let unlimited = optimizer
+        .clone()
+        .owned(bank)
+        .rank_upgrades(&enemies, &prices);
+    assert!(unlimited
+        .iter()
+        .any(|upgrade| upgrade.name == "Scythe of vitur"));
+    assert!(optimizer.rank_upgrades(&enemies, &prices).is_empty());
+    let everything = optimizer
+        .clone()
+        .optimize(enemy)
+        .ok_or("No loadout found")?;
+    let priced = optimizer
+        .budget(0, prices)
+        .optimize(enemy)
+        .ok_or("No loadout found")?;
+    assert_eq!(priced.cost, 0);
+    assert_float_eq(priced.dps, everything.dps);
+    Ok(())
+}